@@ -9,9 +9,11 @@ use bitflags::bitflags;
 use channel::{ApuChannel, Dac, LengthCountedChannel};
 use noise_channel::NoiseChannel;
 use pulse_channel::PulseChannel;
+use serde::{Deserialize, Serialize};
 use wave_channel::WaveChannel;
 
 bitflags! {
+    #[derive(Serialize, Deserialize)]
     struct ChannelsControl: u8 {
         const VIN_LEFT  = 1 << 7;
         const VOL_LEFT  = 7 << 4;
@@ -31,6 +33,7 @@ impl ChannelsControl {
 }
 
 bitflags! {
+    #[derive(Serialize, Deserialize)]
     struct ChannelsSelection :u8 {
         const NOISE_LEFT   = 1 << 7;
         const WAVE_LEFT    = 1 << 6;
@@ -43,6 +46,7 @@ bitflags! {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Apu {
     pulse1: Dac<LengthCountedChannel<PulseChannel>>,
     pulse2: Dac<LengthCountedChannel<PulseChannel>>,
@@ -55,6 +59,9 @@ pub struct Apu {
     power: bool,
 
     sample_counter: f64,
+    /// Samples not yet taken by the frontend, this is output and not part of
+    /// the APU state
+    #[serde(skip)]
     buffer: Vec<f32>,
 
     /// Stores the value of the 4th bit (5th in double speed mode) of the divider
@@ -537,3 +544,5 @@ impl Apu {
         }
     }
 }
+
+impl_savable!(Apu, 1024);
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 pub trait ApuChannel {
     fn output(&self) -> u8;
     fn muted(&self) -> bool;
@@ -8,6 +10,7 @@ pub trait ApuChannel {
     fn trigger(&mut self);
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct LengthCountedChannel<C: ApuChannel> {
     // FIXME: re-order the organization of apu channels,
     //  `dac_enable`, should not be here like this
@@ -124,6 +127,7 @@ impl<C: ApuChannel> ApuChannel for LengthCountedChannel<C> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Dac<C: ApuChannel> {
     capacitor: f32,
     channel: C,
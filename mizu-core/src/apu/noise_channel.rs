@@ -1,7 +1,8 @@
 use super::envelope::EnvelopGenerator;
 use super::ApuChannel;
+use serde::{Deserialize, Serialize};
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct NoiseChannel {
     shift_clock_frequency: u8,
     step_mode_7_bits: bool,
@@ -1,5 +1,6 @@
 use super::envelope::EnvelopGenerator;
 use super::ApuChannel;
+use serde::{Deserialize, Serialize};
 
 const DUTY_CYCLE_SEQUENCES: [[u8; 8]; 4] = [
     [0, 0, 0, 0, 0, 0, 0, 1],
@@ -8,6 +9,7 @@ const DUTY_CYCLE_SEQUENCES: [[u8; 8]; 4] = [
     [0, 1, 1, 1, 1, 1, 1, 0],
 ];
 
+#[derive(Serialize, Deserialize)]
 pub struct PulseChannel {
     sweep_period: u8,
     sweep_current_time: u8,
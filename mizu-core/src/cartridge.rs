@@ -1,19 +1,25 @@
+mod archive;
 mod error;
+mod info;
 mod mappers;
-
-pub use error::CartridgeError;
-
-use error::SramError;
-use mappers::{Mapper, MapperType, MappingResult};
+mod storage;
+
+pub use error::{CartridgeError, CartridgeWarning, SramError};
+pub use info::{CartridgeInfo, CgbSupport, Licensee};
+pub use mappers::{MapperType, CAMERA_HEIGHT, CAMERA_WIDTH};
+pub use storage::{FileSramStorage, MemorySramStorage, SramStorage};
+
+use crate::cheats::RomPatch;
+use crate::infrared::InfraredDevice;
+use crate::save_state::{Savable, SaveError};
+use crate::HeaderValidation;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use mappers::{Mapper, MappingResult};
+use std::cell::RefCell;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
-
-#[derive(Debug, PartialEq)]
-enum TargetDevice {
-    DMG,
-    Color,
-}
+use std::path::Path;
+use std::rc::Rc;
 
 #[derive(Debug)]
 struct CartridgeType {
@@ -145,10 +151,78 @@ impl CartridgeType {
                 ram: true,
                 battery: true,
             }),
+            0xFC => Some(Self {
+                mapper_type: MapperType::PocketCamera,
+                ram: true,
+                battery: true,
+            }),
+            0xFD => Some(Self {
+                mapper_type: MapperType::Tama5,
+                ram: false,
+                battery: true,
+            }),
+            0xFE => Some(Self {
+                mapper_type: MapperType::Huc3,
+                ram: true,
+                battery: true,
+            }),
+            0xFF => Some(Self {
+                mapper_type: MapperType::Huc1,
+                ram: true,
+                battery: true,
+            }),
             _ => None,
         }
     }
 
+    /// Detects the cartridge type of the ROM, unlicensed cartridges don't
+    /// follow the header format, so they are detected with heuristics first
+    fn from_rom(data: &[u8]) -> Option<Self> {
+        let header = info::header(data);
+
+        let mut cartridge_type =
+            Self::detect_unlicensed(data, &header).or_else(|| Self::from_byte(header[0x147]))?;
+
+        // checks if its mbc1 multicart and update the mapper type
+        cartridge_type.update_mbc1_multicart(data);
+
+        Some(cartridge_type)
+    }
+
+    fn detect_unlicensed(data: &[u8], header: &[u8]) -> Option<Self> {
+        let cartridge_type = header[0x147];
+
+        let mapper_type = if info::has_sachen_logo(data) {
+            // MMC2 is used in GBC games
+            if header[0x143] & 0x80 != 0 {
+                MapperType::SachenMmc2
+            } else {
+                MapperType::SachenMmc1
+            }
+        } else if cartridge_type == 0
+            && data.len() > 0x8000
+            && data
+                .windows(11)
+                .any(|w| w == b"WISDOM TREE" || w == b"WISDOM\0TREE")
+        {
+            MapperType::WisdomTree
+        } else if (0x0F..=0x13).contains(&cartridge_type) && selects_mbc3_clone_registers(data) {
+            // the RAM and battery are the same as the MBC3 type in the header
+            return Self::from_byte(cartridge_type).map(|t| Self {
+                mapper_type: MapperType::Mbc3Clone,
+                ..t
+            });
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            mapper_type,
+            ram: false,
+            battery: false,
+        })
+    }
+
     /// Checks if the cartridge is MBC1 multicart and update the `mapper` value
     fn update_mbc1_multicart(&mut self, data: &[u8]) {
         if let MapperType::Mbc1 { ref mut multicart } = self.mapper_type {
@@ -175,31 +249,77 @@ impl CartridgeType {
             MapperType::Mbc2 => Box::new(mappers::Mbc2::default()),
             MapperType::Mbc3 { timer } => Box::new(mappers::Mbc3::new(timer)),
             MapperType::Mbc5 { rumble } => Box::new(mappers::Mbc5::new(rumble)),
-            _ => return None,
+            MapperType::Mmm01 => Box::new(mappers::Mmm01::default()),
+            MapperType::Mbc6 => Box::new(mappers::Mbc6::default()),
+            MapperType::Mbc7 => Box::new(mappers::Mbc7::default()),
+            MapperType::Huc1 => Box::new(mappers::Huc1::default()),
+            MapperType::Huc3 => Box::new(mappers::Huc3::default()),
+            MapperType::PocketCamera => Box::new(mappers::PocketCamera::default()),
+            MapperType::Tama5 => Box::new(mappers::Tama5::default()),
+            MapperType::WisdomTree => Box::new(mappers::WisdomTree::default()),
+            MapperType::SachenMmc1 => Box::new(mappers::Sachen::new(false)),
+            MapperType::SachenMmc2 => Box::new(mappers::Sachen::new(true)),
+            MapperType::Mbc3Clone => Box::new(mappers::Mbc3Clone::new()),
         };
 
         Some(mapper)
     }
 }
 
+/// MBC3 clones select their protection registers with RAM banks `0x0D-0x0F`,
+/// which official MBC3 games never do. The check runs at boot, so only the
+/// code in bank 0 is searched for `LD A, $0D-$0F` followed by
+/// `LD ($4000-$5FFF), A`, and then an access to the selected register at
+/// `$A000-$BFFF` (`LD HL, nn`, `LD A, (nn)` or `LD (nn), A`) right after
+fn selects_mbc3_clone_registers(data: &[u8]) -> bool {
+    /// The number of bytes after the bank select searched for the access
+    const ACCESS_DISTANCE: usize = 8;
+
+    let bank0 = &data[..data.len().min(0x4000)];
+
+    bank0.windows(5).enumerate().any(|(i, w)| {
+        let selects_register = w[0] == 0x3E
+            && (0x0D..=0x0F).contains(&w[1])
+            && w[2] == 0xEA
+            && (0x40..=0x5F).contains(&w[4]);
+
+        let after = &bank0[i + 5..bank0.len().min(i + 5 + ACCESS_DISTANCE)];
+
+        selects_register
+            && after
+                .windows(3)
+                .any(|a| matches!(a[0], 0x21 | 0xEA | 0xFA) && (0xA0..=0xBF).contains(&a[2]))
+    })
+}
+
 pub struct Cartridge {
-    file_path: Box<Path>,
-    game_title: String,
+    /// `None` if the cartridge was loaded from memory and no storage was set,
+    /// in that case the SRAM is not saved
+    sram_storage: Option<Box<dyn SramStorage>>,
+    /// The SRAM was written to since it was last saved
+    sram_dirty: bool,
+    info: CartridgeInfo,
     cartridge_type: CartridgeType,
-    target_device: TargetDevice,
     mapper: Box<dyn Mapper>,
     rom: Vec<u8>,
     ram: Vec<u8>,
+    /// Game Genie cheats applied to ROM reads
+    rom_patches: Vec<RomPatch>,
+    /// Problems found while loading the cartridge or its SRAM
+    warnings: Vec<CartridgeWarning>,
 }
 
 impl Cartridge {
-    pub fn from_file<P: AsRef<Path>>(file_path: P) -> Result<Self, CartridgeError> {
+    pub fn from_file<P: AsRef<Path>>(
+        file_path: P,
+        validation: HeaderValidation,
+    ) -> Result<Self, CartridgeError> {
         let extension = file_path
             .as_ref()
             .extension()
             .ok_or(CartridgeError::ExtensionError)?;
 
-        if extension != "gbc" && extension != "gb" {
+        if extension != "gbc" && extension != "gb" && extension != "zip" && extension != "gz" {
             return Err(CartridgeError::ExtensionError);
         }
 
@@ -208,11 +328,36 @@ impl Cartridge {
         let mut data = Vec::new();
         file.read_to_end(&mut data)?;
 
-        if data.len() < 0x8000 || data.len() % 0x4000 != 0 {
-            eprintln!(
-                "[WARN]: the cartridge contain invalid rom size {:X}",
-                data.len()
-            );
+        let mut cartridge = Self::from_bytes(data, validation)?;
+
+        let storage = FileSramStorage::next_to_rom(file_path);
+        if let Err(err) = cartridge.set_sram_storage(Some(Box::new(storage))) {
+            cartridge
+                .warnings
+                .push(CartridgeWarning::SramLoadFailed(err.to_string()));
+        }
+
+        Ok(cartridge)
+    }
+
+    /// Loads the cartridge from the content of a ROM file, or a zip or gzip
+    /// archive containing a ROM file.
+    ///
+    /// The SRAM is not loaded or saved, use [`set_sram_storage`](Self::set_sram_storage)
+    /// or [`save_battery`](Self::save_battery) and [`load_battery`](Self::load_battery)
+    /// for that.
+    ///
+    /// Invalid header values are reported as errors or warnings depending on
+    /// `validation`.
+    pub fn from_bytes(data: Vec<u8>, validation: HeaderValidation) -> Result<Self, CartridgeError> {
+        let mut data = archive::extract_rom(data)?;
+        let mut warnings = Vec::new();
+
+        // the rom can always be padded, so this is never an error
+        if (data.len() < 0x8000 || data.len() % 0x4000 != 0)
+            && validation != HeaderValidation::Ignore
+        {
+            warnings.push(CartridgeWarning::RomSizePadded(data.len()));
         }
 
         // extend the data, as some roms don't follow the rules :(
@@ -223,79 +368,61 @@ impl Cartridge {
             data.extend_from_slice(&vec![0; 0x4000 - (data.len() % 0x4000)]);
         }
 
-        if &data[0x104..=0x133] != NINTENDO_LOGO_DATA {
-            return Err(CartridgeError::InvalidNintendoLogo);
-        }
-
-        let game_title = String::from_utf8(
-            data[0x134..=0x142]
-                .iter()
-                .copied()
-                .take_while(|e| e != &0)
-                .collect::<Vec<u8>>(),
-        )
-        .map_err(|_| CartridgeError::InvalidGameTitle)?;
-
-        let target_device = if data[0x143] & 0x80 != 0 {
-            TargetDevice::Color
-        } else {
-            TargetDevice::DMG
-        };
-
-        println!("target gameboy {:?}", target_device);
-
-        let mut cartridge_type =
-            CartridgeType::from_byte(data[0x147]).ok_or(CartridgeError::InvalidCartridgeType)?;
+        let info = CartridgeInfo::parse(&data)?;
 
-        // checks if its mbc1 multicart and update the mapper type
-        cartridge_type.update_mbc1_multicart(&data);
-
-        let num_rom_banks = data[0x148];
-        if num_rom_banks > 8 {
-            return Err(CartridgeError::InvalidRomSizeIndex(data[0x148]));
+        if !info.nintendo_logo_valid {
+            validation.report(
+                &mut warnings,
+                CartridgeError::InvalidNintendoLogo,
+                CartridgeWarning::InvalidNintendoLogo,
+            )?;
         }
 
-        let rom_size = 0x8000 << num_rom_banks;
-
-        if rom_size != data.len() {
-            return Err(CartridgeError::InvalidRomSize(rom_size));
+        let cartridge_type =
+            CartridgeType::from_rom(&data).ok_or(CartridgeError::InvalidCartridgeType)?;
+
+        if info.rom_size != data.len() {
+            validation.report(
+                &mut warnings,
+                CartridgeError::InvalidRomSize(info.rom_size),
+                CartridgeWarning::InvalidRomSize {
+                    expected: info.rom_size,
+                    got: data.len(),
+                },
+            )?;
         }
 
-        let ram_size = match data[0x149] {
-            0 => 0,
-            1 => 0x800,
-            2 => 0x2000,
-            3 => 0x8000,
-            4 => 0x20000,
-            5 => 0x10000,
-            _ => {
-                return Err(CartridgeError::InvalidRamSizeIndex(data[0x149]));
-            }
-        };
+        let ram_size = info.ram_size;
 
         if cartridge_type.ram && ram_size == 0 {
             // cartridge_type.ram = false;
             // return Err(CartridgeError::RamNotPresentError);
         } else if !cartridge_type.ram && ram_size != 0 {
-            return Err(CartridgeError::NotNeededRamPresentError);
+            validation.report(
+                &mut warnings,
+                CartridgeError::NotNeededRamPresentError,
+                CartridgeWarning::NotNeededRamPresent,
+            )?;
         }
 
-        let mut ram = vec![0; ram_size];
-
-        let mut checksum = 0u8;
-        for &i in data[0x134..=0x14c].iter() {
-            checksum = checksum.wrapping_sub(i).wrapping_sub(1);
-        }
-
-        if checksum != data[0x14d] {
-            return Err(CartridgeError::InvalidChecksum {
-                got: checksum,
-                expected: data[0x14d],
-            });
+        let ram = vec![0; ram_size];
+
+        if !info.header_checksum_valid {
+            let checksum = info::compute_header_checksum(&data);
+
+            validation.report(
+                &mut warnings,
+                CartridgeError::InvalidChecksum {
+                    got: checksum,
+                    expected: info.header_checksum,
+                },
+                CartridgeWarning::InvalidChecksum {
+                    got: checksum,
+                    expected: info.header_checksum,
+                },
+            )?;
         }
 
-        println!("LOG: {:?}", cartridge_type);
-
         let mut mapper =
             cartridge_type
                 .get_mapper()
@@ -303,47 +430,69 @@ impl Cartridge {
                     cartridge_type.mapper_type,
                 ))?;
 
-        mapper.init((rom_size / 0x4000) as u16, ram_size);
-
-        if cartridge_type.battery {
-            match Self::load_sram_file(file_path.as_ref(), ram_size, mapper.save_battery_size()) {
-                Ok((saved_ram, extra)) => {
-                    ram = saved_ram;
-                    mapper.load_battery(&extra);
-                }
-                Err(err) => eprintln!("ERROR: {}", err),
-            }
-        }
+        // use the real size, in case it does not match the header
+        mapper.init((data.len() / 0x4000) as u16, ram_size);
 
         Ok(Self {
-            file_path: file_path.as_ref().to_path_buf().into_boxed_path(),
-            game_title,
+            sram_storage: None,
+            sram_dirty: false,
+            info,
             cartridge_type,
-            target_device,
             mapper,
             rom: data,
             ram,
+            rom_patches: Vec::new(),
+            warnings,
         })
     }
 
+    /// Notifies the mapper of a CPU read from `0x0000-0x7FFF`, must be called
+    /// before the read
+    pub fn on_cpu_read(&mut self, addr: u16) {
+        self.mapper.on_cpu_read(addr);
+    }
+
+    pub fn skip_boot_rom(&mut self) {
+        self.mapper.skip_boot_rom();
+    }
+
     /// 0x0000-0x3FFF
     pub fn read_rom0(&self, addr: u16) -> u8 {
-        let addr = self.mapper.map_read_rom0(addr);
+        let value = self.rom[self.mapper.map_read_rom0(addr)];
 
-        self.rom[addr]
+        self.patch_rom_read(addr, value)
     }
 
-    // TODO: implement mapper
     /// 0x4000-0x7FFF
     pub fn read_romx(&self, addr: u16) -> u8 {
-        let addr = self.mapper.map_read_romx(addr);
+        match self.mapper.map_read_romx(addr) {
+            MappingResult::Addr(rom_addr) => self.patch_rom_read(addr, self.rom[rom_addr]),
+            // flash memory in MBC6 for example, which cannot be patched
+            MappingResult::Value(value) => value,
+            MappingResult::NotMapped => 0xFF,
+        }
+    }
+
+    #[inline]
+    fn patch_rom_read(&self, addr: u16, value: u8) -> u8 {
+        self.rom_patches
+            .iter()
+            .find_map(|patch| patch.apply(addr, value))
+            .unwrap_or(value)
+    }
 
-        self.rom[addr]
+    pub fn set_rom_patches(&mut self, rom_patches: Vec<RomPatch>) {
+        self.rom_patches = rom_patches;
     }
 
     /// 0x0000-0x7FFF
     pub fn write_to_bank_controller(&mut self, addr: u16, data: u8) {
         self.mapper.write_bank_controller_register(addr, data);
+
+        // MBC6 flash is written through this range
+        if self.mapper.take_battery_written() {
+            self.sram_dirty = true;
+        }
     }
 
     /// 0xA000-0xBFFF
@@ -358,96 +507,250 @@ impl Cartridge {
     /// 0xA000-0xBFFF
     pub fn write_ram(&mut self, addr: u16, data: u8) {
         match self.mapper.map_ram_write(addr, data) {
-            MappingResult::Addr(addr) => self.ram[addr] = data,
-            MappingResult::NotMapped | MappingResult::Value(_) => {}
+            MappingResult::Addr(addr) => {
+                self.ram[addr] = data;
+                self.sram_dirty = true;
+            }
+            MappingResult::NotMapped | MappingResult::Value(_) => {
+                if self.mapper.take_battery_written() {
+                    self.sram_dirty = true;
+                }
+            }
         }
     }
 
+    /// Connects the device to the infrared port of HuC1 and HuC3 cartridges
+    pub fn connect_infrared_device(&mut self, device: Option<Rc<RefCell<dyn InfraredDevice>>>) {
+        self.mapper.connect_infrared_device(device);
+    }
+
+    /// Returns `true` if the rumble motor is on, only used by MBC5 with rumble
+    pub fn rumble_active(&self) -> bool {
+        self.mapper.rumble_active()
+    }
+
+    /// Returns the number of mapper clocks the rumble motor was on since the
+    /// last call
+    pub fn take_rumble_cycles(&mut self) -> u32 {
+        self.mapper.take_rumble_cycles()
+    }
+
+    /// Sets the tilt of the cartridge in units of g, only used by MBC7
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mapper.set_tilt(x, y);
+    }
+
+    /// Sets the image in front of the sensor of the camera cartridge
+    pub fn set_camera_frame(&mut self, frame: &[u8]) {
+        self.mapper.set_camera_frame(frame);
+    }
+
     /// A way to sync bus/emulator to the mapper, main purpose is to sync
     /// MBC3's RTC clock, the number of clocks for one second is 4194304 / 2
     ///
     /// The bus should clock this in 4194304 / 2 clocks per second regardless
     /// of the CPU clock speed (double or normal)
     pub fn clock_mapper(&mut self) {
-        self.mapper.clock();
+        if self.mapper.clock(&mut self.ram) {
+            self.sram_dirty = true;
+        }
     }
 
     pub fn is_cartridge_color(&self) -> bool {
-        self.target_device == TargetDevice::Color
+        self.info.is_cartridge_color()
     }
 
     pub fn game_title(&self) -> &str {
-        &self.game_title
+        &self.info.title
     }
-}
 
-impl Cartridge {
-    fn get_save_file<P: AsRef<Path>>(path: P) -> PathBuf {
-        let extension = path.as_ref().extension().unwrap().to_str().unwrap();
-        path.as_ref().with_extension(format!("{}.sav", extension))
-    }
-
-    fn load_sram_file<P: AsRef<Path>>(
-        path: P,
-        sram_size: usize,
-        extra_size: usize,
-    ) -> Result<(Vec<u8>, Vec<u8>), SramError> {
-        let path = Self::get_save_file(path);
-        println!("Loading SRAM file data from {:?}", path);
-
-        let mut file = File::open(path)?;
-        let mut result = vec![0; sram_size];
-        let mut extra = vec![0; extra_size];
-
-        file.read_exact(&mut result)
-            .map_err(|_| SramError::SramFileSizeDoesNotMatch)?;
-
-        // fail silently, since imported sav files does not support same format
-        // as we does.
-        //
-        // TODO: Maybe we should follow format most emulators follow?
-        if file.read_exact(&mut extra).is_err() {
-            eprintln!("[ERROR] could not read extra information from the save file, this data can be for RTC.");
+    pub fn info(&self) -> &CartridgeInfo {
+        &self.info
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.cartridge_type.battery
+    }
+
+    /// The SRAM followed by the extra data of the mapper (RTC for example),
+    /// this is the content of the `.sav` file
+    pub fn save_battery(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        data.extend_from_slice(&self.mapper.save_battery());
+
+        data
+    }
+
+    /// Loads data saved with [`save_battery`](Self::save_battery) or by other
+    /// emulators, the RTC data at the end can be in the 48 or 44 bytes format
+    /// used by most emulators
+    pub fn load_battery(&mut self, data: &[u8]) -> Result<(), SramError> {
+        if data.len() < self.ram.len() {
+            return Err(SramError::SramFileSizeDoesNotMatch);
+        }
+
+        let (ram, extra) = data.split_at(self.ram.len());
+        self.ram.copy_from_slice(ram);
+        self.sram_dirty = true;
+
+        // not fatal, since the RTC data can be missing or in an unknown
+        // format in save files imported from other emulators
+        if !self.mapper.load_battery(extra) {
+            self.warnings
+                .push(CartridgeWarning::UnknownBatteryExtraData(extra.len()));
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the storage of the SRAM, and loads the SRAM from it if it
+    /// contains saved data.
+    ///
+    /// If the saved data fails to load, the storage is not used, so that the
+    /// saved data is not overwritten.
+    pub fn set_sram_storage(
+        &mut self,
+        mut sram_storage: Option<Box<dyn SramStorage>>,
+    ) -> Result<(), SramError> {
+        if self.cartridge_type.battery {
+            let saved = match sram_storage.as_mut() {
+                Some(storage) => storage.load()?,
+                None => None,
+            };
+
+            if let Some(saved) = saved {
+                self.load_battery(&saved)?;
+            }
+            self.sram_dirty = false;
+        }
+
+        self.sram_storage = sram_storage;
+
+        Ok(())
+    }
+
+    /// Saves the SRAM to the storage if the cartridge has a battery
+    pub fn flush_sram(&mut self) -> Result<(), SramError> {
+        if !self.cartridge_type.battery {
+            return Ok(());
+        }
+
+        let data = self.save_battery();
+        if let Some(storage) = self.sram_storage.as_mut() {
+            storage.save(&data)?;
+            self.sram_dirty = false;
         }
 
-        Ok((result, extra))
+        Ok(())
+    }
+
+    pub fn is_sram_dirty(&self) -> bool {
+        self.sram_dirty
     }
 
-    fn save_sram_file(&self) -> Result<(), SramError> {
-        let path = Self::get_save_file(&self.file_path);
-        println!("Writing SRAM file data to {:?}", path);
+    pub fn warnings(&self) -> &[CartridgeWarning] {
+        &self.warnings
+    }
 
-        let mut file = File::create(&path)?;
+    pub fn add_warning(&mut self, warning: CartridgeWarning) {
+        self.warnings.push(warning);
+    }
 
-        let size = file.write(&self.ram)?;
+    pub fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
 
-        if size != self.ram.len() {
-            file.sync_all()?;
-            // remove the file so it will not be loaded next time the game is run
-            std::fs::remove_file(path).expect("Could not remove `gb.sav` file");
-            return Err(SramError::FailedToSaveSramFile);
+    pub fn ram_len(&self) -> usize {
+        self.ram.len()
+    }
+
+    /// The whole ROM, without going through the mapper
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// The 16KB ROM bank mapped at `0x4000-0x7FFF`, `None` if it is not
+    /// mapped to the ROM (like the flash memory of MBC6)
+    pub fn current_rom_bank(&self) -> Option<u16> {
+        match self.mapper.map_read_romx(0x4000) {
+            MappingResult::Addr(rom_addr) => Some((rom_addr / 0x4000) as u16),
+            _ => None,
         }
+    }
+
+    /// The whole ROM, used for debugging and patching
+    pub fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
 
-        let extra = self.mapper.save_battery();
+    /// The flash memory inside the mapper, only MBC6 has one
+    pub fn flash(&self) -> Option<&[u8]> {
+        self.mapper.flash()
+    }
+
+    pub fn flash_mut(&mut self) -> Option<&mut [u8]> {
+        self.mapper.flash_mut()
+    }
+
+    /// The whole SRAM, without going through the mapper
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        self.sram_dirty = true;
+        &mut self.ram
+    }
+}
+
+/// Saves the SRAM and the mapper state, the ROM is not saved as it
+/// does not change
+impl Savable for Cartridge {
+    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+        writer.write_u32::<LittleEndian>(self.ram.len() as u32)?;
+        writer.write_all(&self.ram)?;
+
+        let mapper_state = self.mapper.save_state()?;
+        writer.write_u32::<LittleEndian>(mapper_state.len() as u32)?;
+        writer.write_all(&mapper_state)?;
 
-        let size = file.write(&extra)?;
+        Ok(())
+    }
 
-        if size != extra.len() {
-            file.sync_all()?;
-            // remove the file so it will not be loaded next time the game is run
-            std::fs::remove_file(path).expect("Could not remove `gb.sav` file");
-            return Err(SramError::FailedToSaveSramFile);
+    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+        let ram_len = reader.read_u32::<LittleEndian>()? as usize;
+        if ram_len != self.ram.len() {
+            return Err(SaveError::LoadSizeDoesNotMatch);
         }
+        reader.read_exact(&mut self.ram)?;
+        self.sram_dirty = true;
+
+        let mapper_state_len = reader.read_u32::<LittleEndian>()? as usize;
+        let mut mapper_state = vec![0; mapper_state_len];
+        reader.read_exact(&mut mapper_state)?;
+        self.mapper.load_state(&mapper_state)?;
 
         Ok(())
     }
+
+    fn object_size() -> u64 {
+        0x21000
+    }
+
+    fn current_save_size(&self) -> Result<u64, SaveError> {
+        let mut tmp_save = Vec::with_capacity(Self::object_size() as usize);
+
+        self.save(&mut tmp_save)?;
+
+        Ok(tmp_save.len() as u64)
+    }
 }
 
 impl Drop for Cartridge {
     fn drop(&mut self) {
-        if self.cartridge_type.battery {
-            self.save_sram_file().unwrap();
-        }
+        // errors cannot be reported here, `flush_sram` should be called
+        // before dropping to handle them
+        let _ = self.flush_sram();
     }
 }
 
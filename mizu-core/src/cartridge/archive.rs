@@ -0,0 +1,60 @@
+use super::error::CartridgeError;
+use flate2::read::GzDecoder;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8; 2] = &[0x1F, 0x8B];
+
+pub fn is_archive(data: &[u8]) -> bool {
+    data.starts_with(ZIP_MAGIC) || data.starts_with(GZIP_MAGIC)
+}
+
+/// Extracts the rom if `data` is a zip or gzip archive, the archive type is
+/// detected from the content and not the file extension. If `data` is not
+/// an archive, it is returned as is.
+pub fn extract_rom(data: Vec<u8>) -> Result<Vec<u8>, CartridgeError> {
+    if data.starts_with(ZIP_MAGIC) {
+        extract_zip(data)
+    } else if data.starts_with(GZIP_MAGIC) {
+        let mut result = Vec::new();
+        GzDecoder::new(data.as_slice()).read_to_end(&mut result)?;
+
+        Ok(result)
+    } else {
+        Ok(data)
+    }
+}
+
+fn is_rom_file(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("gb") || extension.eq_ignore_ascii_case("gbc")
+        })
+}
+
+fn extract_zip(data: Vec<u8>) -> Result<Vec<u8>, CartridgeError> {
+    let map_err = |err: zip::result::ZipError| CartridgeError::ArchiveError(err.to_string());
+
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(map_err)?;
+
+    let mut rom_indices = Vec::new();
+    for i in 0..archive.len() {
+        if is_rom_file(archive.by_index(i).map_err(map_err)?.name()) {
+            rom_indices.push(i);
+        }
+    }
+
+    if rom_indices.len() != 1 {
+        return Err(CartridgeError::ArchiveRomCount(rom_indices.len()));
+    }
+
+    let mut file = archive.by_index(rom_indices[0]).map_err(map_err)?;
+    let mut result = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut result)?;
+
+    Ok(result)
+}
@@ -1,14 +1,22 @@
 use super::mappers::MapperType;
 use std::convert::From;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 use std::io::{Error as ioError, ErrorKind as ioErrorKind};
 
 #[derive(thiserror::Error, Debug)]
 pub enum CartridgeError {
     #[error("File error: {0}")]
     FileError(ioError),
-    #[error("The file ends with an invalid extension, should end with '.gb'")]
+    #[error(
+        "The file ends with an invalid extension, should end with '.gb', '.gbc', '.zip' or '.gz'"
+    )]
     ExtensionError,
+    #[error("Could not extract the rom from the archive: {0}")]
+    ArchiveError(String),
+    #[error("The archive should contain exactly one rom file, but it contains {0}")]
+    ArchiveRomCount(usize),
+    #[error("The rom file is too small to contain a header")]
+    MissingHeader,
     #[error("The rom file does not contain a valid nintendo logo data at 0x104")]
     InvalidNintendoLogo,
     #[error("The game title contain invalid UTF-8 characters")]
@@ -29,6 +37,78 @@ pub enum CartridgeError {
     InvalidChecksum { expected: u8, got: u8 },
     #[error("The mapper {0:?} is not yet implemented")]
     MapperNotImplemented(MapperType),
+    #[error("The boot rom size {got} bytes does not match the expected {expected} bytes")]
+    InvalidBootRomSize { expected: usize, got: usize },
+}
+
+/// Problems in the cartridge that are not fatal, these are reported instead
+/// of [`CartridgeError`] depending on the [`HeaderValidation`](crate::HeaderValidation)
+/// policy, or always for problems that can be recovered from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CartridgeWarning {
+    /// The file size is not a multiple of 16KB or is less than 32KB, the rom
+    /// is padded with zeros. Reported with both `Strict` and `Warn` policies
+    RomSizePadded(usize),
+    InvalidNintendoLogo,
+    /// The size of the rom does not match the size in the header
+    InvalidRomSize {
+        expected: usize,
+        got: usize,
+    },
+    /// The cartridge type does not have RAM, but the header has RAM size
+    NotNeededRamPresent,
+    InvalidChecksum {
+        expected: u8,
+        got: u8,
+    },
+    /// The save file could not be loaded, the game starts with empty SRAM
+    /// and the save file is not overwritten
+    SramLoadFailed(String),
+    /// The data after the SRAM in the save file is not in a known format,
+    /// so the RTC is not restored
+    UnknownBatteryExtraData(usize),
+    /// The cheats file next to the rom could not be loaded, no cheats are
+    /// enabled
+    CheatsLoadFailed(String),
+}
+
+impl Display for CartridgeWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::RomSizePadded(size) => write!(
+                f,
+                "The rom size {:X} is less than 32KB or not a multiple of 16KB, the rom is padded with zeros",
+                size
+            ),
+            Self::InvalidNintendoLogo => write!(
+                f,
+                "The rom file does not contain a valid nintendo logo data at 0x104"
+            ),
+            Self::InvalidRomSize { expected, got } => write!(
+                f,
+                "The file size {} bytes does not match the rom size {} bytes indicated inside the header",
+                got, expected
+            ),
+            Self::NotNeededRamPresent => write!(
+                f,
+                "The cartridge type suggest the cartridge does not have ram, but it is present"
+            ),
+            Self::InvalidChecksum { expected, got } => write!(
+                f,
+                "The header of the cartridge check sum {} does not match the expected {}",
+                got, expected
+            ),
+            Self::SramLoadFailed(error) => write!(f, "Could not load the save file: {}", error),
+            Self::UnknownBatteryExtraData(size) => write!(
+                f,
+                "Could not read the {} bytes of extra information in the save file, this data can be for RTC",
+                size
+            ),
+            Self::CheatsLoadFailed(error) => {
+                write!(f, "Could not load the cheats file: {}", error)
+            }
+        }
+    }
 }
 
 impl From<ioError> for CartridgeError {
@@ -45,8 +125,8 @@ pub enum SramError {
     SramFileSizeDoesNotMatch,
     #[error("Could not save cartridge save file")]
     FailedToSaveSramFile,
-    #[error("Unknown error occured while trying to save/load cartridge save file")]
-    Others,
+    #[error("Unknown error occured while trying to save/load cartridge save file: {0}")]
+    Others(ioError),
 }
 
 impl From<ioError> for SramError {
@@ -54,7 +134,7 @@ impl From<ioError> for SramError {
         match from.kind() {
             ioErrorKind::NotFound => Self::NoSramFileFound,
             ioErrorKind::PermissionDenied => Self::FailedToSaveSramFile,
-            _ => Self::Others,
+            _ => Self::Others(from),
         }
     }
 }
@@ -0,0 +1,280 @@
+use super::archive;
+use super::error::CartridgeError;
+use super::mappers::{unscramble_address, MapperType};
+use super::{CartridgeType, NINTENDO_LOGO_DATA};
+use std::borrow::Cow;
+
+/// The support of the Gameboy Color features, from the CGB flag at `0x143`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport {
+    /// A DMG game
+    None,
+    /// Supports CGB features, but works on DMG as well
+    Compatible,
+    /// Only works on CGB
+    Only,
+}
+
+/// The publisher of the game, older games use one byte at `0x14B`, and newer
+/// games use two ASCII characters at `0x144-0x145` (in which case `0x14B` is
+/// `0x33`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Licensee {
+    Old(u8),
+    New(String),
+}
+
+/// The information in the header of a cartridge, which can be parsed without
+/// loading the game
+#[derive(Debug, Clone)]
+pub struct CartridgeInfo {
+    pub title: String,
+    pub cgb_support: CgbSupport,
+    /// Supports Super Gameboy features
+    pub sgb_support: bool,
+    /// The raw cartridge type at `0x147`
+    pub cartridge_type: u8,
+    /// `None` if the cartridge type is unknown
+    pub mapper: Option<MapperType>,
+    pub has_ram: bool,
+    pub has_battery: bool,
+    /// The size of the ROM in bytes as indicated in the header
+    pub rom_size: usize,
+    /// The size of the RAM in bytes as indicated in the header
+    pub ram_size: usize,
+    pub licensee: Licensee,
+    /// Japanese games have a destination code of `0`
+    pub is_japanese: bool,
+    pub version: u8,
+    pub nintendo_logo_valid: bool,
+    pub header_checksum: u8,
+    pub header_checksum_valid: bool,
+    pub global_checksum: u16,
+    /// The global checksum is not checked by the Gameboy, so it can be
+    /// invalid in some homebrew games
+    pub global_checksum_valid: bool,
+}
+
+impl CartridgeInfo {
+    /// Parses the header of a ROM file, or of the ROM in a zip or gzip
+    /// archive
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CartridgeError> {
+        if archive::is_archive(data) {
+            Self::parse(&archive::extract_rom(data.to_vec())?)
+        } else {
+            Self::parse(data)
+        }
+    }
+
+    pub(super) fn parse(data: &[u8]) -> Result<Self, CartridgeError> {
+        if data.len() < 0x150 {
+            return Err(CartridgeError::MissingHeader);
+        }
+
+        let header = header(data);
+
+        let title = String::from_utf8(
+            header[0x134..=0x142]
+                .iter()
+                .copied()
+                .take_while(|e| e != &0)
+                .collect::<Vec<u8>>(),
+        )
+        .map_err(|_| CartridgeError::InvalidGameTitle)?;
+
+        let cgb_support = match header[0x143] {
+            0xC0 => CgbSupport::Only,
+            flag if flag & 0x80 != 0 => CgbSupport::Compatible,
+            _ => CgbSupport::None,
+        };
+
+        let cartridge_type = CartridgeType::from_rom(data);
+
+        let num_rom_banks = header[0x148];
+        if num_rom_banks > 8 {
+            return Err(CartridgeError::InvalidRomSizeIndex(num_rom_banks));
+        }
+
+        let ram_size = match header[0x149] {
+            0 => 0,
+            1 => 0x800,
+            2 => 0x2000,
+            3 => 0x8000,
+            4 => 0x20000,
+            5 => 0x10000,
+            _ => {
+                return Err(CartridgeError::InvalidRamSizeIndex(header[0x149]));
+            }
+        };
+
+        let licensee = if header[0x14B] == 0x33 {
+            Licensee::New(String::from_utf8_lossy(&header[0x144..=0x145]).into_owned())
+        } else {
+            Licensee::Old(header[0x14B])
+        };
+
+        let global_checksum = data
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != 0x14E && i != 0x14F)
+            .fold(0u16, |sum, (_, &byte)| sum.wrapping_add(byte as u16));
+
+        Ok(Self {
+            title,
+            cgb_support,
+            sgb_support: header[0x146] == 0x03,
+            cartridge_type: header[0x147],
+            mapper: cartridge_type.as_ref().map(|t| t.mapper_type),
+            has_ram: cartridge_type.as_ref().is_some_and(|t| t.ram),
+            has_battery: cartridge_type.as_ref().is_some_and(|t| t.battery),
+            rom_size: 0x8000 << num_rom_banks,
+            ram_size,
+            licensee,
+            is_japanese: header[0x14A] == 0,
+            version: header[0x14C],
+            nintendo_logo_valid: &header[0x104..=0x133] == NINTENDO_LOGO_DATA,
+            header_checksum: header[0x14D],
+            header_checksum_valid: compute_header_checksum(data) == header[0x14D],
+            global_checksum: u16::from_be_bytes([header[0x14E], header[0x14F]]),
+            global_checksum_valid: global_checksum
+                == u16::from_be_bytes([header[0x14E], header[0x14F]]),
+        })
+    }
+
+    /// Returns `true` if the game supports CGB features
+    pub fn is_cartridge_color(&self) -> bool {
+        self.cgb_support != CgbSupport::None
+    }
+}
+
+/// MMM01 multicarts boot into a menu in the last 32KB of the ROM, so the
+/// header used is the one of the menu, while the header at the start of the
+/// ROM is for the first game
+pub(super) fn header_offset(data: &[u8]) -> usize {
+    if data.len() > 0x8000 {
+        let offset = data.len() - 0x8000;
+        let header = &data[offset..];
+
+        if (0x0B..=0x0D).contains(&header[0x147]) && &header[0x104..=0x133] == NINTENDO_LOGO_DATA {
+            return offset;
+        }
+    }
+
+    0
+}
+
+/// Sachen cartridges have their own logo in the header, and the Nintendo logo
+/// at `0x0184`, all scrambled (see [`unscramble_address`])
+pub(super) fn has_sachen_logo(data: &[u8]) -> bool {
+    data.len() >= 0x200
+        && &data[0x104..=0x133] != NINTENDO_LOGO_DATA
+        && (0..0x30)
+            .all(|i| data[unscramble_address(0x184 + i) as usize] == NINTENDO_LOGO_DATA[i as usize])
+}
+
+/// The header as seen by the gameboy, the data starting at `0x0000` of the
+/// header is returned, as the header is at `0x100-0x14F`
+pub(super) fn header(data: &[u8]) -> Cow<'_, [u8]> {
+    if has_sachen_logo(data) {
+        let mut header = data[..0x150].to_vec();
+        for addr in 0x100..0x150 {
+            header[addr] = data[unscramble_address(addr as u16) as usize];
+        }
+        // the logo is read from `0x0184` while the cartridge is locked
+        for addr in 0x104..=0x133 {
+            header[addr] = data[unscramble_address(addr as u16 | 0x80) as usize];
+        }
+
+        Cow::Owned(header)
+    } else {
+        Cow::Borrowed(&data[header_offset(data)..])
+    }
+}
+
+/// The checksum of `0x134-0x14C`, which should match the byte at `0x14D`
+/// for the game to boot on real hardware
+pub(super) fn compute_header_checksum(data: &[u8]) -> u8 {
+    header(data)[0x134..=0x14C]
+        .iter()
+        .fold(0u8, |checksum, &i| checksum.wrapping_sub(i).wrapping_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom_with_header(size: usize, cartridge_type: u8) -> Vec<u8> {
+        let mut rom = vec![0; size];
+        rom[0x104..=0x133].copy_from_slice(NINTENDO_LOGO_DATA);
+        rom[0x134..0x138].copy_from_slice(b"GAME");
+        rom[0x147] = cartridge_type;
+        rom
+    }
+
+    #[test]
+    fn detects_tama5() {
+        let info = CartridgeInfo::parse(&rom_with_header(0x8000, 0xFD)).unwrap();
+        assert_eq!(info.mapper, Some(MapperType::Tama5));
+        assert!(info.has_battery);
+    }
+
+    #[test]
+    fn detects_sachen() {
+        let plain = rom_with_header(0x8000, 0);
+        let mut rom = plain.clone();
+
+        // scramble the header, and put another logo in place of Nintendo's
+        for addr in 0x100..0x150 {
+            rom[unscramble_address(addr) as usize] = plain[addr as usize];
+        }
+        for addr in 0x104..=0x133 {
+            rom[unscramble_address(addr) as usize] = 0x55;
+            rom[unscramble_address(addr | 0x80) as usize] = plain[addr as usize];
+        }
+
+        let info = CartridgeInfo::parse(&rom).unwrap();
+        assert_eq!(info.mapper, Some(MapperType::SachenMmc1));
+        assert_eq!(info.title, "GAME");
+        assert!(info.nintendo_logo_valid);
+
+        // a GBC game
+        rom[unscramble_address(0x143) as usize] = 0x80;
+        let info = CartridgeInfo::parse(&rom).unwrap();
+        assert_eq!(info.mapper, Some(MapperType::SachenMmc2));
+    }
+
+    #[test]
+    fn detects_wisdom_tree() {
+        let mut rom = rom_with_header(0x10000, 0);
+        rom[0x200..0x20B].copy_from_slice(b"WISDOM TREE");
+
+        let info = CartridgeInfo::parse(&rom).unwrap();
+        assert_eq!(info.mapper, Some(MapperType::WisdomTree));
+    }
+
+    #[test]
+    fn detects_mbc3_clone() {
+        let mut rom = rom_with_header(0x10000, 0x13);
+        let info = CartridgeInfo::parse(&rom).unwrap();
+        assert_eq!(info.mapper, Some(MapperType::Mbc3 { timer: false }));
+
+        // LD A, $0D; LD ($4000), A; LD A, ($A000)
+        rom[0x200..0x208].copy_from_slice(&[0x3E, 0x0D, 0xEA, 0x00, 0x40, 0xFA, 0x00, 0xA0]);
+        let info = CartridgeInfo::parse(&rom).unwrap();
+        assert_eq!(info.mapper, Some(MapperType::Mbc3Clone));
+        assert!(info.has_ram && info.has_battery);
+    }
+
+    #[test]
+    fn mbc3_clone_pattern_outside_code() {
+        let mut rom = rom_with_header(0x10000, 0x13);
+
+        // a bank select without a register access, e.g. in graphics data
+        rom[0x200..0x205].copy_from_slice(&[0x3E, 0x0D, 0xEA, 0x00, 0x40]);
+        // the full sequence, but outside of bank 0
+        rom[0x4200..0x4208].copy_from_slice(&[0x3E, 0x0D, 0xEA, 0x00, 0x40, 0xFA, 0x00, 0xA0]);
+
+        let info = CartridgeInfo::parse(&rom).unwrap();
+        assert_eq!(info.mapper, Some(MapperType::Mbc3 { timer: false }));
+    }
+}
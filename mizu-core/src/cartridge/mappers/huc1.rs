@@ -0,0 +1,182 @@
+use super::{Mapper, MappingResult};
+use crate::infrared::{InfraredDevice, InfraredPort};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Hudson's HuC1, similar to MBC1, but the RAM window can be switched to
+/// the infrared port
+#[derive(Serialize, Deserialize)]
+pub struct Huc1 {
+    rom_banks: u16,
+    ram_banks: u8,
+
+    rom_bank: u8,
+    ram_bank: u8,
+
+    /// `0x0E` written to `0x0000-0x1FFF` maps the infrared port in
+    /// `0xA000-0xBFFF` instead of the RAM
+    ir_mode: bool,
+    infrared: InfraredPort,
+}
+
+impl Default for Huc1 {
+    fn default() -> Self {
+        Self {
+            rom_banks: 0,
+            ram_banks: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            ir_mode: false,
+            infrared: InfraredPort::default(),
+        }
+    }
+}
+
+impl Huc1 {
+    fn map_ram(&self, addr: u16) -> MappingResult {
+        if self.ram_banks == 0 {
+            return MappingResult::NotMapped;
+        }
+
+        let bank = self.ram_bank % self.ram_banks;
+        MappingResult::Addr(bank as usize * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+}
+
+impl Mapper for Huc1 {
+    fn init(&mut self, rom_banks: u16, ram_size: usize) {
+        self.rom_banks = rom_banks;
+        self.ram_banks = (ram_size / 0x2000) as u8;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        let addr = addr & 0x3FFF;
+
+        let bank = self.rom_bank as u16 % self.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        if self.ir_mode {
+            // 0xC1 if light is received, 0xC0 otherwise
+            MappingResult::Value(0xC0 | self.infrared.receive_light() as u8)
+        } else {
+            self.map_ram(addr)
+        }
+    }
+
+    fn map_ram_write(&mut self, addr: u16, data: u8) -> MappingResult {
+        if self.ir_mode {
+            self.infrared.set_light(data & 1 != 0);
+            MappingResult::NotMapped
+        } else {
+            self.map_ram(addr)
+        }
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ir_mode = data & 0xF == 0xE,
+            0x2000..=0x3FFF => {
+                self.rom_bank = data & 0x3F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank = data & 0x3,
+            _ => {}
+        }
+    }
+
+    fn connect_infrared_device(&mut self, device: Option<Rc<RefCell<dyn InfraredDevice>>>) {
+        self.infrared.connect_device(device);
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        // the device is not part of the state
+        let device = self.infrared.take_device();
+        let result = load_object(self, data);
+        self.infrared.connect_device(device);
+
+        result
+    }
+}
+
+impl_savable!(Huc1, 32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Loopback {
+        light: bool,
+    }
+
+    impl InfraredDevice for Loopback {
+        fn set_light(&mut self, on: bool) {
+            self.light = on;
+        }
+
+        fn receive_light(&mut self) -> bool {
+            self.light
+        }
+    }
+
+    #[test]
+    fn infrared() {
+        let mut huc1 = Huc1::default();
+        huc1.init(4, 0x8000);
+        let device = Rc::new(RefCell::new(Loopback { light: false }));
+        huc1.connect_infrared_device(Some(device.clone()));
+
+        huc1.write_bank_controller_register(0x0000, 0x0E);
+        assert!(matches!(
+            huc1.map_ram_read(0xA000),
+            MappingResult::Value(0xC0)
+        ));
+
+        huc1.map_ram_write(0xA000, 1);
+        assert!(device.borrow().light);
+        assert!(matches!(
+            huc1.map_ram_read(0xBFFF),
+            MappingResult::Value(0xC1)
+        ));
+
+        // the RAM is mapped again with any other value
+        huc1.write_bank_controller_register(0x0000, 0x0A);
+        assert!(matches!(huc1.map_ram_read(0xA000), MappingResult::Addr(0)));
+        huc1.map_ram_write(0xA000, 0);
+        assert!(device.borrow().light);
+    }
+
+    #[test]
+    fn ram_banks() {
+        let mut huc1 = Huc1::default();
+        huc1.init(4, 0x8000);
+
+        huc1.write_bank_controller_register(0x0000, 0x0A);
+        huc1.write_bank_controller_register(0x4000, 0x02);
+        assert!(matches!(
+            huc1.map_ram_write(0xA010, 0x55),
+            MappingResult::Addr(0x4010)
+        ));
+
+        // only 2 bits are used for the RAM bank
+        huc1.write_bank_controller_register(0x4000, 0x07);
+        assert!(matches!(
+            huc1.map_ram_read(0xA010),
+            MappingResult::Addr(0x6010)
+        ));
+    }
+}
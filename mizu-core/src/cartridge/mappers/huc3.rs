@@ -0,0 +1,386 @@
+use super::{system_time_now, Mapper, MappingResult, ONE_SECOND_MAPPER_CLOCKS};
+use crate::infrared::{InfraredDevice, InfraredPort};
+use crate::save_state::{load_object, save_object, SaveError};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+/// The RTC footer at the end of the `.sav` file, all little endian:
+/// - the UNIX timestamp of the time the file was saved (64bit).
+/// - minutes, days, alarm minutes and alarm days (16bit each).
+/// - alarm enabled (8bit).
+const RTC_FOOTER_SIZE: usize = 17;
+
+const MINUTES_PER_DAY: u16 = 60 * 24;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Huc3Rtc {
+    /// A full second is ONE_SECOND_MAPPER_CLOCKS, which is synced to the bus
+    sub_second: u32,
+    seconds: u8,
+    /// minute of the day
+    minutes: u16,
+    days: u16,
+
+    alarm_minutes: u16,
+    alarm_days: u16,
+    alarm_enabled: bool,
+
+    /// The RTC is accessed one nibble at a time, the time is at `0x00-0x06`
+    /// and the alarm is at `0x58-0x5F`
+    access_index: u8,
+    access_flags: u8,
+    /// result of the last read command
+    read_value: u8,
+}
+
+impl Huc3Rtc {
+    fn add_seconds(&mut self, seconds: u64) {
+        let seconds = self.seconds as u64 + seconds;
+        self.seconds = (seconds % 60) as u8;
+
+        let minutes = self.minutes as u64 + seconds / 60;
+        self.minutes = (minutes % MINUTES_PER_DAY as u64) as u16;
+        self.days = self
+            .days
+            .wrapping_add((minutes / MINUTES_PER_DAY as u64) as u16);
+    }
+
+    fn clock_second_part(&mut self) {
+        self.sub_second += 1;
+
+        if self.sub_second == ONE_SECOND_MAPPER_CLOCKS {
+            self.sub_second = 0;
+            self.add_seconds(1);
+        }
+    }
+
+    /// Returns the register and the position of the nibble at `index`
+    fn register_mut(&mut self, index: u8) -> Option<(&mut u16, u8)> {
+        match index {
+            0x00..=0x02 => Some((&mut self.minutes, index)),
+            0x03..=0x06 => Some((&mut self.days, index - 0x03)),
+            0x58..=0x5A => Some((&mut self.alarm_minutes, index - 0x58)),
+            0x5B..=0x5E => Some((&mut self.alarm_days, index - 0x5B)),
+            _ => None,
+        }
+    }
+
+    fn read_nibble(&mut self, index: u8) -> u8 {
+        if index == 0x5F {
+            return self.alarm_enabled as u8;
+        }
+
+        match self.register_mut(index) {
+            Some((register, nibble)) => ((*register >> (nibble * 4)) & 0xF) as u8,
+            None => 0,
+        }
+    }
+
+    fn write_nibble(&mut self, index: u8, value: u8) {
+        if index == 0x5F {
+            self.alarm_enabled = value & 1 != 0;
+            return;
+        }
+
+        if let Some((register, nibble)) = self.register_mut(index) {
+            *register &= !(0xF << (nibble * 4));
+            *register |= (value as u16 & 0xF) << (nibble * 4);
+        }
+    }
+
+    /// The upper nibble is the command, and the lower nibble is its argument
+    fn command(&mut self, data: u8) {
+        let argument = data & 0xF;
+
+        match data >> 4 {
+            // read and increment
+            0x1 => {
+                self.read_value = self.read_nibble(self.access_index);
+                self.access_index = self.access_index.wrapping_add(1);
+            }
+            // write
+            0x2 => self.write_nibble(self.access_index, argument),
+            // write and increment
+            0x3 => {
+                self.write_nibble(self.access_index, argument);
+                self.access_index = self.access_index.wrapping_add(1);
+            }
+            0x4 => self.access_index = (self.access_index & 0xF0) | argument,
+            0x5 => self.access_index = (self.access_index & 0x0F) | (argument << 4),
+            0x6 => self.access_flags = argument,
+            _ => {}
+        }
+    }
+
+    fn read_response(&self) -> u8 {
+        if self.access_flags == 0x2 {
+            // ready
+            1
+        } else {
+            self.read_value
+        }
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        let mut cur = Cursor::new(Vec::with_capacity(RTC_FOOTER_SIZE));
+
+        cur.write_u64::<LittleEndian>(system_time_now()).unwrap();
+        for value in &[self.minutes, self.days, self.alarm_minutes, self.alarm_days] {
+            cur.write_u16::<LittleEndian>(*value).unwrap();
+        }
+        cur.write_u8(self.alarm_enabled as u8).unwrap();
+
+        cur.into_inner()
+    }
+
+    /// Returns `false` if the data is not in a known format
+    fn load_battery(&mut self, data: &[u8]) -> bool {
+        if data.len() != RTC_FOOTER_SIZE {
+            return false;
+        }
+
+        let mut cur = Cursor::new(data);
+        let timestamp = cur.read_u64::<LittleEndian>().unwrap();
+        self.minutes = cur.read_u16::<LittleEndian>().unwrap() % MINUTES_PER_DAY;
+        self.days = cur.read_u16::<LittleEndian>().unwrap();
+        self.alarm_minutes = cur.read_u16::<LittleEndian>().unwrap();
+        self.alarm_days = cur.read_u16::<LittleEndian>().unwrap();
+        self.alarm_enabled = cur.read_u8().unwrap() & 1 != 0;
+
+        // the clock keeps running while the game is not played
+        if timestamp != 0 {
+            self.add_seconds(system_time_now().saturating_sub(timestamp));
+        }
+
+        true
+    }
+}
+
+/// Hudson's HuC3, has an RTC, a speaker and an infrared port, which are
+/// accessed through `0xA000-0xBFFF` depending on the mode selected
+#[derive(Serialize, Deserialize)]
+pub struct Huc3 {
+    rom_banks: u16,
+    ram_banks: u8,
+
+    rom_bank: u8,
+    ram_bank: u8,
+
+    /// - `0x0`: RAM read only
+    /// - `0xA`: RAM read/write
+    /// - `0xB`: RTC command write
+    /// - `0xC`: RTC response read
+    /// - `0xD`: RTC semaphore
+    /// - `0xE`: infrared
+    mode: u8,
+
+    rtc: Huc3Rtc,
+    infrared: InfraredPort,
+}
+
+impl Default for Huc3 {
+    fn default() -> Self {
+        Self {
+            rom_banks: 0,
+            ram_banks: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            mode: 0,
+            rtc: Huc3Rtc::default(),
+            infrared: InfraredPort::default(),
+        }
+    }
+}
+
+impl Huc3 {
+    fn map_ram(&self, addr: u16) -> MappingResult {
+        if self.ram_banks == 0 {
+            return MappingResult::NotMapped;
+        }
+
+        let bank = self.ram_bank % self.ram_banks;
+        MappingResult::Addr(bank as usize * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+}
+
+impl Mapper for Huc3 {
+    fn init(&mut self, rom_banks: u16, ram_size: usize) {
+        self.rom_banks = rom_banks;
+        self.ram_banks = (ram_size / 0x2000) as u8;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        let addr = addr & 0x3FFF;
+
+        let bank = self.rom_bank as u16 % self.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        match self.mode {
+            0x0 | 0xA => self.map_ram(addr),
+            0xC => MappingResult::Value(self.rtc.read_response()),
+            // commands are executed instantly, so always ready
+            0xD => MappingResult::Value(1),
+            0xE => MappingResult::Value(0xC0 | self.infrared.receive_light() as u8),
+            _ => MappingResult::NotMapped,
+        }
+    }
+
+    fn map_ram_write(&mut self, addr: u16, data: u8) -> MappingResult {
+        match self.mode {
+            0xA => return self.map_ram(addr),
+            0xB => self.rtc.command(data),
+            0xE => self.infrared.set_light(data & 1 != 0),
+            _ => {}
+        }
+
+        MappingResult::NotMapped
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.mode = data & 0xF,
+            0x2000..=0x3FFF => {
+                self.rom_bank = data & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank = data & 0x3,
+            _ => {}
+        }
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.rtc.save_battery()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) -> bool {
+        self.rtc.load_battery(data)
+    }
+
+    fn connect_infrared_device(&mut self, device: Option<Rc<RefCell<dyn InfraredDevice>>>) {
+        self.infrared.connect_device(device);
+    }
+
+    fn clock(&mut self, _ram: &mut [u8]) -> bool {
+        self.rtc.clock_second_part();
+
+        false
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        // the device is not part of the state
+        let device = self.infrared.take_device();
+        let result = load_object(self, data);
+        self.infrared.connect_device(device);
+
+        result
+    }
+}
+
+impl_savable!(Huc3, 64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Loopback {
+        light: bool,
+    }
+
+    impl InfraredDevice for Loopback {
+        fn set_light(&mut self, on: bool) {
+            self.light = on;
+        }
+
+        fn receive_light(&mut self) -> bool {
+            self.light
+        }
+    }
+
+    fn rtc_read(huc3: &mut Huc3) -> u8 {
+        huc3.write_bank_controller_register(0x0000, 0xB);
+        huc3.map_ram_write(0xA000, 0x10);
+        huc3.write_bank_controller_register(0x0000, 0xC);
+        match huc3.map_ram_read(0xA000) {
+            MappingResult::Value(value) => value,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn rtc_commands() {
+        let mut huc3 = Huc3::default();
+        huc3.init(4, 0x8000);
+        huc3.rtc.minutes = 0x123;
+        huc3.rtc.days = 0x0456;
+
+        huc3.write_bank_controller_register(0x0000, 0xB);
+        // address 0x00
+        huc3.map_ram_write(0xA000, 0x40);
+        huc3.map_ram_write(0xA000, 0x50);
+
+        let nibbles = (0..7).map(|_| rtc_read(&mut huc3)).collect::<Vec<_>>();
+        assert_eq!(nibbles, vec![0x3, 0x2, 0x1, 0x6, 0x5, 0x4, 0x0]);
+
+        // write to address 0x03 (days low nibble)
+        huc3.write_bank_controller_register(0x0000, 0xB);
+        huc3.map_ram_write(0xA000, 0x43);
+        huc3.map_ram_write(0xA000, 0x29);
+        assert_eq!(huc3.rtc.days, 0x0459);
+    }
+
+    #[test]
+    fn rtc_footer_adds_elapsed_time() {
+        let mut huc3 = Huc3::default();
+        huc3.rtc.minutes = MINUTES_PER_DAY - 1;
+        huc3.rtc.days = 10;
+
+        let mut footer = huc3.save_battery();
+        assert_eq!(footer.len(), RTC_FOOTER_SIZE);
+        // saved two minutes ago
+        let timestamp = system_time_now() - 120;
+        footer[..8].copy_from_slice(&timestamp.to_le_bytes());
+
+        let mut loaded = Huc3::default();
+        assert!(loaded.load_battery(&footer));
+        assert_eq!(loaded.rtc.minutes, 1);
+        assert_eq!(loaded.rtc.days, 11);
+    }
+
+    #[test]
+    fn infrared() {
+        let mut huc3 = Huc3::default();
+        huc3.init(4, 0x8000);
+        let device = Rc::new(RefCell::new(Loopback { light: false }));
+        huc3.connect_infrared_device(Some(device.clone()));
+
+        huc3.write_bank_controller_register(0x0000, 0xE);
+        huc3.map_ram_write(0xA000, 1);
+        assert!(device.borrow().light);
+        assert!(matches!(
+            huc3.map_ram_read(0xA000),
+            MappingResult::Value(0xC1)
+        ));
+
+        // the device stays connected after loading a state
+        let state = huc3.save_state().unwrap();
+        huc3.load_state(&state).unwrap();
+        huc3.map_ram_write(0xA000, 0);
+        assert!(!device.borrow().light);
+    }
+}
@@ -1,5 +1,8 @@
 use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
 pub struct Mbc1 {
     is_2k_ram: bool,
     ram_banks: u8,
@@ -59,13 +62,13 @@ impl Mapper for Mbc1 {
         bank * 0x4000 + addr as usize
     }
 
-    fn map_read_romx(&self, addr: u16) -> usize {
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
         let addr = addr & 0x3FFF;
 
         let bank = (self.rom_bank1 | (self.two_bit_bank2 << self.bank2_shift())) as usize;
         let bank = bank % self.rom_banks as usize;
 
-        bank as usize * 0x4000 + addr as usize
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
     }
 
     fn map_ram_read(&mut self, addr: u16) -> MappingResult {
@@ -117,4 +120,14 @@ impl Mapper for Mbc1 {
             _ => {}
         }
     }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
 }
+
+impl_savable!(Mbc1, 32);
@@ -1,4 +1,7 @@
 use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, Savable, SaveError};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
 
 pub struct Mbc2 {
     rom_banks: u8,
@@ -10,6 +13,9 @@ pub struct Mbc2 {
     ram: [u8; 512],
 
     ram_enable: bool,
+
+    /// the internal ram was modified since it was last saved
+    ram_written: bool,
 }
 
 impl Default for Mbc2 {
@@ -19,6 +25,7 @@ impl Default for Mbc2 {
             rom_bank_4000: 1,
             ram: [0; 512],
             ram_enable: false,
+            ram_written: false,
         }
     }
 }
@@ -34,12 +41,12 @@ impl Mapper for Mbc2 {
         addr as usize
     }
 
-    fn map_read_romx(&self, addr: u16) -> usize {
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
         let addr = addr & 0x3FFF;
 
         let bank = self.rom_bank_4000 % self.rom_banks;
 
-        bank as usize * 0x4000 + addr as usize
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
     }
 
     fn map_ram_read(&mut self, addr: u16) -> MappingResult {
@@ -53,6 +60,7 @@ impl Mapper for Mbc2 {
     fn map_ram_write(&mut self, addr: u16, data: u8) -> MappingResult {
         if self.ram_enable {
             self.ram[addr as usize & 0x1FF] = data & 0xF;
+            self.ram_written = true;
         }
 
         MappingResult::NotMapped
@@ -71,17 +79,57 @@ impl Mapper for Mbc2 {
         }
     }
 
-    fn save_battery_size(&self) -> usize {
-        512
-    }
-
     fn save_battery(&self) -> Vec<u8> {
         self.ram.into()
     }
 
-    fn load_battery(&mut self, data: &[u8]) {
-        assert!(data.len() == 512);
+    fn load_battery(&mut self, data: &[u8]) -> bool {
+        if data.len() < 512 {
+            return false;
+        }
+
+        self.ram.copy_from_slice(&data[..512]);
+        true
+    }
+
+    fn take_battery_written(&mut self) -> bool {
+        std::mem::replace(&mut self.ram_written, false)
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl Savable for Mbc2 {
+    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+        writer.write_u8(self.rom_banks)?;
+        writer.write_u8(self.rom_bank_4000)?;
+        writer.write_u8(self.ram_enable as u8)?;
+        writer.write_all(&self.ram)?;
+
+        Ok(())
+    }
+
+    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+        self.rom_banks = reader.read_u8()?;
+        self.rom_bank_4000 = reader.read_u8()?;
+        self.ram_enable = reader.read_u8()? != 0;
+        reader.read_exact(&mut self.ram)?;
+
+        Ok(())
+    }
+
+    fn object_size() -> u64 {
+        0x400
+    }
 
-        self.ram.copy_from_slice(data);
+    fn current_save_size(&self) -> Result<u64, SaveError> {
+        // + 3 for `rom_banks`, `rom_bank_4000` and `ram_enable`
+        Ok(self.ram.len() as u64 + 3)
     }
 }
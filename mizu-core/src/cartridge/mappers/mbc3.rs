@@ -1,15 +1,25 @@
-use super::{Mapper, MappingResult, ONE_SECOND_MAPPER_CLOCKS};
+use super::{system_time_now, Mapper, MappingResult, ONE_SECOND_MAPPER_CLOCKS};
+use crate::save_state::{load_object, save_object, SaveError};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 use std::io::Cursor;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-fn system_time_now() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_else(|e| e.duration())
-        .as_secs()
-}
 
+/// The size of the RTC footer at the end of `.sav` files used by most
+/// emulators (VBA-M, BGB, SameBoy, ...), the footer is:
+/// - the current seconds, minutes, hours, days low and days high registers,
+///   each as a 32bit little endian value.
+/// - the latched registers in the same format.
+/// - the UNIX timestamp of the time the file was saved, as a 64bit little
+///   endian value.
+const RTC_FOOTER_SIZE: usize = 48;
+/// Same as [`RTC_FOOTER_SIZE`], but with a 32bit timestamp, used by older
+/// emulators
+const RTC_FOOTER_SIZE_32BIT_TIMESTAMP: usize = 44;
+/// The size of the RTC data saved by older versions of mizu
+const LEGACY_RTC_SIZE: usize = 21;
+
+#[derive(Clone, Serialize, Deserialize)]
 struct RtcRegister {
     /// A full second is ONE_SECOND_MAPPER_CLOCKS, which is synced to the bus
     sub_second: u32,
@@ -149,30 +159,88 @@ impl RtcRegister {
         self.days &= 0x1FF;
     }
 
-    fn save_battery_size(&self) -> usize {
-        std::mem::size_of::<u8>() * 3 + std::mem::size_of::<u16>() + std::mem::size_of::<u64>() * 2
+    fn days_high(&self) -> u8 {
+        ((self.day_counter_carry as u8) << 7) | ((self.halt as u8) << 6) | (self.days >> 8) as u8
+    }
+
+    fn write_footer_registers(&self, cur: &mut Cursor<Vec<u8>>) {
+        for &value in &[
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            self.days_high(),
+        ] {
+            cur.write_u32::<LittleEndian>(value as u32).unwrap();
+        }
     }
 
     fn save_battery(&self) -> Vec<u8> {
-        let result = Vec::with_capacity(self.save_battery_size());
-        let mut cur = Cursor::new(result);
-
-        cur.write_u8(self.seconds).unwrap();
-        cur.write_u8(self.minutes).unwrap();
-        cur.write_u8(self.hours).unwrap();
-        cur.write_u16::<LittleEndian>(self.days).unwrap();
-        cur.write_u64::<LittleEndian>(self.last_latched_time)
-            .unwrap();
-        cur.write_u64::<LittleEndian>(self.current_time_secs.saturating_sub(system_time_now()))
-            .unwrap();
+        // the registers are only updated when read, so update them to the
+        // current time without affecting the latched values
+        let mut current = self.clone();
+        current.latched = false;
+        current.update_registers();
+
+        let mut cur = Cursor::new(Vec::with_capacity(RTC_FOOTER_SIZE));
+
+        current.write_footer_registers(&mut cur);
+        self.write_footer_registers(&mut cur);
+        cur.write_u64::<LittleEndian>(system_time_now()).unwrap();
 
         let result = cur.into_inner();
-        assert_eq!(result.len(), self.save_battery_size());
+        assert_eq!(result.len(), RTC_FOOTER_SIZE);
 
         result
     }
 
-    fn load_battery(&mut self, data: &[u8]) {
+    /// Returns `false` if the data is not in a known format
+    fn load_battery(&mut self, data: &[u8]) -> bool {
+        match data.len() {
+            RTC_FOOTER_SIZE | RTC_FOOTER_SIZE_32BIT_TIMESTAMP => {
+                self.load_footer(data);
+                true
+            }
+            LEGACY_RTC_SIZE => {
+                self.load_legacy(data);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn load_footer(&mut self, data: &[u8]) {
+        let mut cur = Cursor::new(data);
+        let mut read = || cur.read_u32::<LittleEndian>().unwrap();
+
+        // only the current registers are used, the latched registers are
+        // ignored as the latch is cleared when the cartridge is loaded
+        self.seconds = read() as u8 & 0x3F;
+        self.minutes = read() as u8 & 0x3F;
+        self.hours = read() as u8 & 0x1F;
+        let days_low = read() as u8;
+        let days_high = read() as u8;
+        self.days = ((days_high as u16 & 1) << 8) | days_low as u16;
+        self.halt = (days_high >> 6) & 1 == 1;
+        self.day_counter_carry = (days_high >> 7) & 1 == 1;
+
+        let timestamp = if data.len() == RTC_FOOTER_SIZE {
+            u64::from_le_bytes(data[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(data[40..44].try_into().unwrap()) as u64
+        };
+
+        // the time passed since the file was saved is added to the
+        // registers the next time they are updated
+        let elapsed = system_time_now().saturating_sub(timestamp);
+        self.last_latched_time = if timestamp == 0 {
+            self.current_time_secs
+        } else {
+            self.current_time_secs.saturating_sub(elapsed)
+        };
+    }
+
+    fn load_legacy(&mut self, data: &[u8]) {
         let mut cur = Cursor::new(data);
 
         self.seconds = cur.read_u8().unwrap();
@@ -196,7 +264,7 @@ impl RtcRegister {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Mbc3 {
     rom_banks: u16,
     is_2k_ram: bool,
@@ -265,12 +333,12 @@ impl Mapper for Mbc3 {
         addr as usize
     }
 
-    fn map_read_romx(&self, addr: u16) -> usize {
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
         let addr = addr & 0x3FFF;
 
         let bank = self.rom_bank_4000 % self.rom_banks;
 
-        bank as usize * 0x4000 + addr as usize
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
     }
 
     fn map_ram_read(&mut self, addr: u16) -> MappingResult {
@@ -336,14 +404,6 @@ impl Mapper for Mbc3 {
         }
     }
 
-    fn save_battery_size(&self) -> usize {
-        if self.rtc_present {
-            self.rtc_register.save_battery_size()
-        } else {
-            0
-        }
-    }
-
     fn save_battery(&self) -> Vec<u8> {
         if self.rtc_present {
             self.rtc_register.save_battery()
@@ -352,13 +412,202 @@ impl Mapper for Mbc3 {
         }
     }
 
-    fn load_battery(&mut self, data: &[u8]) {
+    fn load_battery(&mut self, data: &[u8]) -> bool {
         if self.rtc_present {
             self.rtc_register.load_battery(data)
+        } else {
+            true
         }
     }
 
-    fn clock(&mut self) {
+    fn clock(&mut self, _ram: &mut [u8]) -> bool {
         self.rtc_register.clock_second_part();
+
+        false
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(Mbc3, 128);
+
+/// MBC3 clone used in unlicensed games like Pokemon Jade and Diamond, it has
+/// no RTC, instead selecting the RAM banks `0x0D-0x0F` maps two protection
+/// registers and a command register, which the game uses to check that it is
+/// running on the original cartridge
+#[derive(Serialize, Deserialize)]
+pub struct Mbc3Clone {
+    mbc3: Mbc3,
+
+    /// `Some(0..=2)` if a protection register is mapped instead of the RAM
+    protection_register: Option<u8>,
+    protection_values: [u8; 2],
+}
+
+impl Mbc3Clone {
+    pub fn new() -> Self {
+        Self {
+            mbc3: Mbc3::new(false),
+            protection_register: None,
+            protection_values: [0; 2],
+        }
+    }
+
+    fn protection_command(&mut self, command: u8) {
+        let [a, b] = &mut self.protection_values;
+
+        match command {
+            0x11 => *a = a.wrapping_sub(1),
+            0x12 => *b = b.wrapping_sub(1),
+            0x41 => *a = a.wrapping_add(*b),
+            0x42 => *b = b.wrapping_add(*a),
+            0x51 => *a = a.wrapping_add(1),
+            0x52 => *b = b.wrapping_add(1),
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mbc3Clone {
+    fn init(&mut self, rom_banks: u16, ram_size: usize) {
+        self.mbc3.init(rom_banks, ram_size);
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        self.mbc3.map_read_rom0(addr)
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        self.mbc3.map_read_romx(addr)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        match self.protection_register {
+            Some(_) if !self.mbc3.ram_block_enable => MappingResult::NotMapped,
+            Some(register @ 0..=1) => {
+                MappingResult::Value(self.protection_values[register as usize])
+            }
+            Some(_) => MappingResult::Value(0),
+            None => self.mbc3.map_ram_read(addr),
+        }
+    }
+
+    fn map_ram_write(&mut self, addr: u16, data: u8) -> MappingResult {
+        match self.protection_register {
+            Some(_) if !self.mbc3.ram_block_enable => {}
+            Some(register @ 0..=1) => self.protection_values[register as usize] = data,
+            Some(_) => self.protection_command(data),
+            None => return self.mbc3.map_ram_write(addr, data),
+        }
+
+        MappingResult::NotMapped
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        if let 0x4000..=0x5FFF = addr {
+            if (0x0D..=0x0F).contains(&(data & 0xF)) {
+                self.protection_register = Some((data & 0xF) - 0x0D);
+                return;
+            }
+
+            self.protection_register = None;
+        }
+
+        self.mbc3.write_bank_controller_register(addr, data);
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(Mbc3Clone, 128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clone_protection_registers() {
+        let mut mbc3 = Mbc3Clone::new();
+        mbc3.init(64, 0x8000);
+
+        let read = |mbc3: &mut Mbc3Clone| match mbc3.map_ram_read(0xA000) {
+            MappingResult::Value(value) => value,
+            _ => unreachable!(),
+        };
+
+        mbc3.write_bank_controller_register(0x0000, 0x0A);
+        mbc3.write_bank_controller_register(0x4000, 0x0D);
+        mbc3.map_ram_write(0xA000, 5);
+        mbc3.write_bank_controller_register(0x4000, 0x0E);
+        mbc3.map_ram_write(0xA000, 3);
+
+        // a += b, then b--
+        mbc3.write_bank_controller_register(0x4000, 0x0F);
+        mbc3.map_ram_write(0xA000, 0x41);
+        mbc3.map_ram_write(0xA000, 0x12);
+
+        mbc3.write_bank_controller_register(0x4000, 0x0D);
+        assert_eq!(read(&mut mbc3), 8);
+        mbc3.write_bank_controller_register(0x4000, 0x0E);
+        assert_eq!(read(&mut mbc3), 2);
+
+        // back to RAM
+        mbc3.write_bank_controller_register(0x4000, 0x01);
+        assert!(matches!(
+            mbc3.map_ram_read(0xA000),
+            MappingResult::Addr(0x2000)
+        ));
+    }
+
+    #[test]
+    fn rtc_footer_round_trip() {
+        let mut rtc = RtcRegister::default();
+        rtc.write_register(0, 12);
+        rtc.write_register(1, 34);
+        rtc.write_register(2, 5);
+        rtc.write_register(3, 0x23);
+        rtc.write_register(4, 0x01);
+
+        let data = rtc.save_battery();
+        assert_eq!(data.len(), RTC_FOOTER_SIZE);
+        assert_eq!(
+            &data[..20],
+            &[12, 0, 0, 0, 34, 0, 0, 0, 5, 0, 0, 0, 0x23, 0, 0, 0, 1, 0, 0, 0]
+        );
+
+        let mut loaded = RtcRegister::default();
+        assert!(loaded.load_battery(&data));
+        assert_eq!(loaded.read_register(1), 34);
+        assert_eq!(loaded.read_register(2), 5);
+        assert_eq!(loaded.read_register(3), 0x23);
+        assert_eq!(loaded.read_register(4), 0x01);
+    }
+
+    #[test]
+    fn rtc_footer_adds_elapsed_time() {
+        let mut data = vec![0; RTC_FOOTER_SIZE_32BIT_TIMESTAMP];
+        // 10 minutes, saved one hour ago
+        data[4] = 10;
+        let timestamp = (system_time_now() - 60 * 60) as u32;
+        data[40..44].copy_from_slice(&timestamp.to_le_bytes());
+
+        let mut rtc = RtcRegister::default();
+        assert!(rtc.load_battery(&data));
+        assert_eq!(rtc.read_register(1), 10);
+        assert_eq!(rtc.read_register(2), 1);
+
+        assert!(!rtc.load_battery(&[0; 10]));
     }
 }
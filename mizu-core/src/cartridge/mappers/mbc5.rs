@@ -1,6 +1,8 @@
 use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct Mbc5 {
     rom_banks: u16,
     is_2k_ram: bool,
@@ -10,14 +12,20 @@ pub struct Mbc5 {
     ram_bank: u8,
     rom_bank: u16,
 
-    // TODO: use this idk how
-    _rumble: bool,
+    /// The cartridge has a rumble motor, controlled by bit 3 of the RAM bank
+    rumble: bool,
+    rumble_active: bool,
+    /// The number of clocks the motor was on since the last
+    /// [`Mapper::take_rumble_cycles`], games turn it on and off many times
+    /// per frame to control its strength
+    #[serde(skip)]
+    rumble_cycles: u32,
 }
 
 impl Mbc5 {
     pub fn new(rumble: bool) -> Self {
         Self {
-            _rumble: rumble,
+            rumble,
             rom_bank: 1,
             ..Self::default()
         }
@@ -36,12 +44,12 @@ impl Mapper for Mbc5 {
         addr as usize
     }
 
-    fn map_read_romx(&self, addr: u16) -> usize {
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
         let addr = addr & 0x3FFF;
 
         let bank = self.rom_bank % self.rom_banks;
 
-        bank as usize * 0x4000 + addr as usize
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
     }
 
     fn map_ram_read(&mut self, addr: u16) -> MappingResult {
@@ -78,9 +86,100 @@ impl Mapper for Mbc5 {
                 self.rom_bank |= ((data & 1) as u16) << 8;
             }
             0x4000..=0x5FFF => {
-                self.ram_bank = data & 0xF;
+                if self.rumble {
+                    self.rumble_active = data & 0x8 != 0;
+                    self.ram_bank = data & 0x7;
+                } else {
+                    self.ram_bank = data & 0xF;
+                }
             }
             _ => {}
         }
     }
+
+    fn rumble_active(&self) -> bool {
+        self.rumble_active
+    }
+
+    fn take_rumble_cycles(&mut self) -> u32 {
+        std::mem::take(&mut self.rumble_cycles)
+    }
+
+    fn clock(&mut self, _ram: &mut [u8]) -> bool {
+        if self.rumble_active {
+            self.rumble_cycles = self.rumble_cycles.saturating_add(1);
+        }
+
+        false
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(Mbc5, 32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rumble_motor() {
+        let mut mbc5 = Mbc5::new(true);
+        mbc5.init(64, 0x8000);
+
+        mbc5.write_bank_controller_register(0x0000, 0x0A);
+        mbc5.write_bank_controller_register(0x4000, 0x09);
+        assert!(mbc5.rumble_active());
+        // the motor bit is not part of the RAM bank
+        assert!(matches!(
+            mbc5.map_ram_read(0xA000),
+            MappingResult::Addr(0x2000)
+        ));
+
+        mbc5.write_bank_controller_register(0x4000, 0x01);
+        assert!(!mbc5.rumble_active());
+    }
+
+    #[test]
+    fn rumble_cycles() {
+        let mut mbc5 = Mbc5::new(true);
+        mbc5.init(64, 0x8000);
+
+        // the motor is on for 10 of 30 clocks, and off when queried
+        for i in 0..30 {
+            let motor_bit = if i % 3 == 0 { 0x08 } else { 0x00 };
+            mbc5.write_bank_controller_register(0x4000, motor_bit);
+            mbc5.clock(&mut []);
+        }
+        assert!(!mbc5.rumble_active());
+        assert_eq!(mbc5.take_rumble_cycles(), 10);
+        assert_eq!(mbc5.take_rumble_cycles(), 0);
+
+        // does not overflow if it is never taken
+        mbc5.rumble_cycles = u32::MAX - 1;
+        mbc5.write_bank_controller_register(0x4000, 0x08);
+        mbc5.clock(&mut []);
+        mbc5.clock(&mut []);
+        assert_eq!(mbc5.take_rumble_cycles(), u32::MAX);
+    }
+
+    #[test]
+    fn no_rumble_motor() {
+        let mut mbc5 = Mbc5::new(false);
+        mbc5.init(64, 0x20000);
+
+        mbc5.write_bank_controller_register(0x0000, 0x0A);
+        mbc5.write_bank_controller_register(0x4000, 0x09);
+        assert!(!mbc5.rumble_active());
+        assert!(matches!(
+            mbc5.map_ram_read(0xA000),
+            MappingResult::Addr(0x12000)
+        ));
+    }
 }
@@ -0,0 +1,366 @@
+use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// The Macronix MX29F008 flash chip, 128 banks of 8KB
+const FLASH_SIZE: usize = 0x100000;
+const FLASH_MANUFACTURER_ID: u8 = 0xC2;
+const FLASH_DEVICE_ID: u8 = 0x81;
+
+/// The state of the flash command sequence, every command starts with
+/// writing `0xAA` to `0x5555` and `0x55` to `0x2AAA`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum FlashMode {
+    #[default]
+    Read,
+    Unlock1,
+    Unlock2,
+    /// Reads return the manufacturer and device IDs
+    Id,
+    /// The next write programs one byte
+    Program,
+    EraseSetup,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+/// The registers are saved separately from the flash, so that the 1MB of
+/// flash is not serialized by bincode byte by byte
+#[derive(Default, Serialize, Deserialize)]
+struct Registers {
+    /// number of 8KB banks
+    rom_banks: u16,
+    /// number of 4KB banks
+    ram_banks: u8,
+
+    ram_enable: bool,
+    /// `0xA000-0xAFFF` and `0xB000-0xBFFF`
+    ram_banks_ab: [u8; 2],
+
+    flash_enable: bool,
+    flash_write_enable: bool,
+    /// `0x4000-0x5FFF` and `0x6000-0x7FFF`, each window can map ROM or
+    /// flash
+    rom_banks_ab: [u8; 2],
+    flash_selected_ab: [bool; 2],
+
+    flash_mode: FlashMode,
+}
+
+impl_savable!(Registers, 32);
+
+pub struct Mbc6 {
+    registers: Registers,
+    flash: Vec<u8>,
+    /// the flash was modified since it was last saved
+    flash_written: bool,
+}
+
+impl Default for Mbc6 {
+    fn default() -> Self {
+        Self {
+            registers: Registers {
+                // same as a 16KB bank 1
+                rom_banks_ab: [2, 3],
+                ..Registers::default()
+            },
+            flash: vec![0xFF; FLASH_SIZE],
+            flash_written: false,
+        }
+    }
+}
+
+impl Mbc6 {
+    /// `0` for `0x4000-0x5FFF` and `1` for `0x6000-0x7FFF`
+    fn rom_window(addr: u16) -> usize {
+        ((addr >> 13) & 1) as usize
+    }
+
+    fn flash_addr(&self, addr: u16) -> Option<usize> {
+        let window = Self::rom_window(addr);
+
+        if self.registers.flash_enable && self.registers.flash_selected_ab[window] {
+            let bank = (self.registers.rom_banks_ab[window] & 0x7F) as usize;
+            Some(bank * 0x2000 + (addr & 0x1FFF) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn read_flash(&self, flash_addr: usize) -> u8 {
+        if self.registers.flash_mode == FlashMode::Id {
+            match flash_addr & 0xFF {
+                0 => FLASH_MANUFACTURER_ID,
+                1 => FLASH_DEVICE_ID,
+                // sectors are not protected
+                _ => 0,
+            }
+        } else {
+            // programming and erasing are instant, so there is no status
+            // to report
+            self.flash[flash_addr]
+        }
+    }
+
+    fn write_flash(&mut self, flash_addr: usize, data: u8) {
+        // the chip only checks the lower 15 bits of command addresses
+        let command_addr = flash_addr & 0x7FFF;
+
+        self.registers.flash_mode = match self.registers.flash_mode {
+            FlashMode::Program => {
+                // programming can only clear bits
+                self.flash[flash_addr] &= data;
+                self.flash_written = true;
+                FlashMode::Read
+            }
+            _ if data == 0xF0 => FlashMode::Read,
+            FlashMode::Read | FlashMode::Id if command_addr == 0x5555 && data == 0xAA => {
+                FlashMode::Unlock1
+            }
+            FlashMode::Read | FlashMode::Id => self.registers.flash_mode,
+            FlashMode::Unlock1 if command_addr == 0x2AAA && data == 0x55 => FlashMode::Unlock2,
+            FlashMode::Unlock2 if command_addr == 0x5555 => match data {
+                0x90 => FlashMode::Id,
+                0xA0 => FlashMode::Program,
+                0x80 => FlashMode::EraseSetup,
+                _ => FlashMode::Read,
+            },
+            FlashMode::EraseSetup if command_addr == 0x5555 && data == 0xAA => {
+                FlashMode::EraseUnlock1
+            }
+            FlashMode::EraseUnlock1 if command_addr == 0x2AAA && data == 0x55 => {
+                FlashMode::EraseUnlock2
+            }
+            FlashMode::EraseUnlock2 => {
+                match data {
+                    // chip erase
+                    0x10 if command_addr == 0x5555 => self.erase_flash(0..FLASH_SIZE),
+                    0x30 => self.erase_flash(Self::flash_sector(flash_addr)),
+                    _ => {}
+                }
+                FlashMode::Read
+            }
+            _ => FlashMode::Read,
+        };
+    }
+
+    fn erase_flash(&mut self, range: Range<usize>) {
+        self.flash[range].iter_mut().for_each(|b| *b = 0xFF);
+        self.flash_written = true;
+    }
+
+    /// The sectors are 64KB, except for the last one which is split into
+    /// 32KB, 8KB, 8KB and 16KB sectors (top boot block)
+    fn flash_sector(flash_addr: usize) -> Range<usize> {
+        match flash_addr {
+            0x00000..=0xEFFFF => {
+                let start = flash_addr & !0xFFFF;
+                start..start + 0x10000
+            }
+            0xF0000..=0xF7FFF => 0xF0000..0xF8000,
+            0xF8000..=0xF9FFF => 0xF8000..0xFA000,
+            0xFA000..=0xFBFFF => 0xFA000..0xFC000,
+            _ => 0xFC000..0x100000,
+        }
+    }
+
+    fn map_ram(&self, addr: u16) -> MappingResult {
+        if !self.registers.ram_enable || self.registers.ram_banks == 0 {
+            return MappingResult::NotMapped;
+        }
+
+        let window = ((addr >> 12) & 1) as usize;
+        let bank = (self.registers.ram_banks_ab[window] % self.registers.ram_banks) as usize;
+
+        MappingResult::Addr(bank * 0x1000 + (addr & 0xFFF) as usize)
+    }
+}
+
+impl Mapper for Mbc6 {
+    fn init(&mut self, rom_banks: u16, ram_size: usize) {
+        self.registers.rom_banks = rom_banks * 2;
+        self.registers.ram_banks = (ram_size / 0x1000) as u8;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        if let Some(flash_addr) = self.flash_addr(addr) {
+            return MappingResult::Value(self.read_flash(flash_addr));
+        }
+
+        let window = Self::rom_window(addr);
+        if self.registers.flash_selected_ab[window] {
+            // flash is selected but disabled
+            return MappingResult::NotMapped;
+        }
+
+        let bank = self.registers.rom_banks_ab[window] as u16 % self.registers.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        self.map_ram(addr)
+    }
+
+    fn map_ram_write(&mut self, addr: u16, _data: u8) -> MappingResult {
+        self.map_ram(addr)
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        let registers = &mut self.registers;
+
+        match addr {
+            0x0000..=0x03FF => registers.ram_enable = data & 0xF == 0xA,
+            0x0400..=0x07FF => registers.ram_banks_ab[0] = data & 0x7,
+            0x0800..=0x0BFF => registers.ram_banks_ab[1] = data & 0x7,
+            0x0C00..=0x0FFF => registers.flash_enable = data & 1 != 0,
+            0x1000 => registers.flash_write_enable = data & 1 != 0,
+            0x2000..=0x27FF => registers.rom_banks_ab[0] = data & 0x7F,
+            0x2800..=0x2FFF => registers.flash_selected_ab[0] = data == 0x08,
+            0x3000..=0x37FF => registers.rom_banks_ab[1] = data & 0x7F,
+            0x3800..=0x3FFF => registers.flash_selected_ab[1] = data == 0x08,
+            0x4000..=0x7FFF if registers.flash_write_enable => {
+                if let Some(flash_addr) = self.flash_addr(addr) {
+                    self.write_flash(flash_addr, data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.flash.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) -> bool {
+        if data.len() != FLASH_SIZE {
+            return false;
+        }
+
+        self.flash.copy_from_slice(data);
+        true
+    }
+
+    fn take_battery_written(&mut self) -> bool {
+        std::mem::replace(&mut self.flash_written, false)
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(&self.registers)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(&mut self.registers, data)
+    }
+
+    /// Saved in save states, so that loading a state or rewinding does not
+    /// keep the flash content of the future
+    fn flash(&self) -> Option<&[u8]> {
+        Some(&self.flash)
+    }
+
+    fn flash_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.flash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_state::Savable;
+
+    fn write_command(mbc6: &mut Mbc6, command: u8) {
+        // flash bank 2 at 0x4000 and flash bank 1 at 0x6000
+        mbc6.write_bank_controller_register(0x4000 + 0x1555, 0xAA);
+        mbc6.write_bank_controller_register(0x6000 + 0x0AAA, 0x55);
+        mbc6.write_bank_controller_register(0x4000 + 0x1555, command);
+    }
+
+    fn read(mbc6: &Mbc6, addr: u16) -> u8 {
+        match mbc6.map_read_romx(addr) {
+            MappingResult::Value(value) => value,
+            _ => panic!("flash should be mapped"),
+        }
+    }
+
+    fn setup() -> Mbc6 {
+        let mut mbc6 = Mbc6::default();
+        mbc6.init(64, 0x8000);
+        mbc6.write_bank_controller_register(0x0C00, 1);
+        mbc6.write_bank_controller_register(0x1000, 1);
+        mbc6.write_bank_controller_register(0x2000, 2);
+        mbc6.write_bank_controller_register(0x2800, 8);
+        mbc6.write_bank_controller_register(0x3000, 1);
+        mbc6.write_bank_controller_register(0x3800, 8);
+        mbc6
+    }
+
+    #[test]
+    fn flash_id() {
+        let mut mbc6 = setup();
+
+        write_command(&mut mbc6, 0x90);
+        assert_eq!(read(&mbc6, 0x4000), FLASH_MANUFACTURER_ID);
+        assert_eq!(read(&mbc6, 0x4001), FLASH_DEVICE_ID);
+
+        mbc6.write_bank_controller_register(0x4000, 0xF0);
+        assert_eq!(read(&mbc6, 0x4000), 0xFF);
+    }
+
+    #[test]
+    fn flash_program_and_erase() {
+        let mut mbc6 = setup();
+
+        write_command(&mut mbc6, 0xA0);
+        mbc6.write_bank_controller_register(0x4010, 0x5A);
+        assert_eq!(read(&mbc6, 0x4010), 0x5A);
+        assert_eq!(mbc6.flash[0x4010], 0x5A);
+        assert!(mbc6.take_battery_written());
+
+        // writes without a command are ignored
+        mbc6.write_bank_controller_register(0x4011, 0x00);
+        assert_eq!(read(&mbc6, 0x4011), 0xFF);
+
+        write_command(&mut mbc6, 0x80);
+        mbc6.write_bank_controller_register(0x4000 + 0x1555, 0xAA);
+        mbc6.write_bank_controller_register(0x6000 + 0x0AAA, 0x55);
+        mbc6.write_bank_controller_register(0x4010, 0x30);
+        assert_eq!(read(&mbc6, 0x4010), 0xFF);
+    }
+
+    #[test]
+    fn flash_outside_save_state() {
+        let mut mbc6 = setup();
+
+        write_command(&mut mbc6, 0xA0);
+        mbc6.write_bank_controller_register(0x4010, 0x5A);
+        let state = mbc6.save_state().unwrap();
+        let flash = mbc6.flash().unwrap().to_vec();
+        assert_eq!(state.len(), Registers::object_size() as usize);
+
+        write_command(&mut mbc6, 0xA0);
+        mbc6.write_bank_controller_register(0x4010, 0x00);
+        mbc6.write_bank_controller_register(0x2000, 5);
+        assert_eq!(read(&mbc6, 0x4010), 0xFF);
+
+        mbc6.load_state(&state).unwrap();
+        mbc6.flash_mut().unwrap().copy_from_slice(&flash);
+        assert_eq!(read(&mbc6, 0x4010), 0x5A);
+    }
+
+    #[test]
+    fn flash_write_protected() {
+        let mut mbc6 = setup();
+        mbc6.write_bank_controller_register(0x1000, 0);
+
+        write_command(&mut mbc6, 0xA0);
+        mbc6.write_bank_controller_register(0x4010, 0x5A);
+        assert_eq!(read(&mbc6, 0x4010), 0xFF);
+        assert!(!mbc6.take_battery_written());
+    }
+}
@@ -0,0 +1,450 @@
+use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
+
+/// The value of the accelerometer when the cartridge is flat
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+/// The change in the accelerometer value for a tilt of 1g
+const ACCELEROMETER_ONE_G: f32 = 0x70 as f32;
+
+/// 256 words, the 93LC56 has only 128 words, but the address sent by the
+/// games has 8 bits in both chips, so this works for the 93LC66 as well
+const EEPROM_SIZE: usize = 0x200;
+const EEPROM_ADDRESS_BITS: u8 = 8;
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+enum EepromState {
+    /// Waiting for the start bit
+    #[default]
+    Idle,
+    /// Receiving the opcode and the address
+    Command { value: u16, bits: u8 },
+    /// Sending the data of `addr`, `remaining` is the number of bits left
+    /// of the current word
+    Read { addr: u8, remaining: u8 },
+    /// Receiving the data to write to `addr`, or to all words if `None`
+    Write {
+        addr: Option<u8>,
+        value: u16,
+        bits: u8,
+    },
+    /// The command is finished, and any more bits are ignored until `CS`
+    /// goes low
+    Done,
+}
+
+/// The 93LC56 serial EEPROM, which is accessed one bit at a time through the
+/// pins `CS`, `CLK`, `DI` and `DO`
+#[derive(Default, Serialize, Deserialize)]
+struct Eeprom {
+    /// 16 bit words stored in little endian, like other emulators
+    data: Vec<u8>,
+    write_enabled: bool,
+
+    cs: bool,
+    clk: bool,
+    di: bool,
+    do_: bool,
+
+    state: EepromState,
+    /// The data was modified since it was last saved
+    written: bool,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Self {
+            data: vec![0xFF; EEPROM_SIZE],
+            do_: true,
+            ..Self::default()
+        }
+    }
+
+    fn read_word(&self, addr: u8) -> u16 {
+        let index = addr as usize * 2 % EEPROM_SIZE;
+        u16::from_le_bytes([self.data[index], self.data[index + 1]])
+    }
+
+    fn write_word(&mut self, addr: u8, value: u16) {
+        if !self.write_enabled {
+            return;
+        }
+
+        let index = addr as usize * 2 % EEPROM_SIZE;
+        self.data[index..index + 2].copy_from_slice(&value.to_le_bytes());
+        self.written = true;
+    }
+
+    fn write_all(&mut self, value: u16) {
+        for addr in 0..(EEPROM_SIZE / 2) as u8 {
+            self.write_word(addr, value);
+        }
+    }
+
+    fn read_pins(&self) -> u8 {
+        (self.cs as u8) << 7 | (self.clk as u8) << 6 | (self.di as u8) << 1 | self.do_ as u8
+    }
+
+    fn write_pins(&mut self, data: u8) {
+        let cs = data & 0x80 != 0;
+        let clk = data & 0x40 != 0;
+        self.di = data & 0x02 != 0;
+
+        if !cs {
+            self.state = EepromState::Idle;
+        } else if !self.cs {
+            // selecting the chip starts a new command
+            self.state = EepromState::Idle;
+            self.do_ = true;
+        } else if clk && !self.clk {
+            self.clock_bit(self.di);
+        }
+
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    /// Handles one bit of `DI` on the rising edge of `CLK`
+    fn clock_bit(&mut self, bit: bool) {
+        self.state = match self.state {
+            EepromState::Idle if bit => EepromState::Command { value: 0, bits: 0 },
+            EepromState::Idle => EepromState::Idle,
+            EepromState::Command { value, bits } => {
+                let value = value << 1 | bit as u16;
+                let bits = bits + 1;
+
+                if bits == EEPROM_ADDRESS_BITS + 2 {
+                    self.execute(value)
+                } else {
+                    EepromState::Command { value, bits }
+                }
+            }
+            EepromState::Read {
+                mut addr,
+                mut remaining,
+            } => {
+                // sequential reads continue to the next word
+                if remaining == 0 {
+                    addr = addr.wrapping_add(1);
+                    remaining = 16;
+                }
+                remaining -= 1;
+                self.do_ = (self.read_word(addr) >> remaining) & 1 != 0;
+
+                EepromState::Read { addr, remaining }
+            }
+            EepromState::Write { addr, value, bits } => {
+                let value = value << 1 | bit as u16;
+                let bits = bits + 1;
+
+                if bits == 16 {
+                    match addr {
+                        Some(addr) => self.write_word(addr, value),
+                        None => self.write_all(value),
+                    }
+                    // writes are instant, so always ready
+                    self.do_ = true;
+                    EepromState::Done
+                } else {
+                    EepromState::Write { addr, value, bits }
+                }
+            }
+            EepromState::Done => EepromState::Done,
+        };
+    }
+
+    fn execute(&mut self, command: u16) -> EepromState {
+        let addr = command as u8;
+
+        match command >> EEPROM_ADDRESS_BITS {
+            // READ, starts with a dummy 0 bit
+            0b10 => {
+                self.do_ = false;
+                EepromState::Read {
+                    addr,
+                    remaining: 16,
+                }
+            }
+            // WRITE
+            0b01 => EepromState::Write {
+                addr: Some(addr),
+                value: 0,
+                bits: 0,
+            },
+            // ERASE
+            0b11 => {
+                self.write_word(addr, 0xFFFF);
+                self.do_ = true;
+                EepromState::Done
+            }
+            _ => match addr >> (EEPROM_ADDRESS_BITS - 2) {
+                // EWDS
+                0b00 => {
+                    self.write_enabled = false;
+                    EepromState::Done
+                }
+                // WRAL
+                0b01 => EepromState::Write {
+                    addr: None,
+                    value: 0,
+                    bits: 0,
+                },
+                // ERAL
+                0b10 => {
+                    self.write_all(0xFFFF);
+                    self.do_ = true;
+                    EepromState::Done
+                }
+                // EWEN
+                _ => {
+                    self.write_enabled = true;
+                    EepromState::Done
+                }
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Mbc7 {
+    rom_banks: u16,
+    rom_bank: u8,
+
+    /// RAM is enabled by writing `0x0A` to `0x0000-0x1FFF` and `0x40` to
+    /// `0x4000-0x5FFF`
+    ram_enable_1: bool,
+    ram_enable_2: bool,
+
+    /// Writing `0x55` to `Ax0x` allows the next `0xAA` write to `Ax1x`
+    /// to latch the accelerometer
+    latch_ready: bool,
+    x_latch: u16,
+    y_latch: u16,
+
+    /// The tilt in g, provided by the frontend, it is not part of the
+    /// cartridge state
+    #[serde(skip)]
+    tilt: (f32, f32),
+
+    eeprom: Eeprom,
+}
+
+impl Default for Mbc7 {
+    fn default() -> Self {
+        Self {
+            rom_banks: 0,
+            rom_bank: 1,
+            ram_enable_1: false,
+            ram_enable_2: false,
+            latch_ready: false,
+            x_latch: 0x8000,
+            y_latch: 0x8000,
+            tilt: (0., 0.),
+            eeprom: Eeprom::new(),
+        }
+    }
+}
+
+impl Mbc7 {
+    fn latch_accelerometer(&mut self) {
+        let (x, y) = self.tilt;
+
+        // the X axis value decreases when tilting to the right
+        self.x_latch = (ACCELEROMETER_CENTER - x * ACCELEROMETER_ONE_G) as u16;
+        self.y_latch = (ACCELEROMETER_CENTER + y * ACCELEROMETER_ONE_G) as u16;
+    }
+}
+
+impl Mapper for Mbc7 {
+    fn init(&mut self, rom_banks: u16, _ram_size: usize) {
+        assert!(rom_banks <= 256);
+        self.rom_banks = rom_banks;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        let addr = addr & 0x3FFF;
+
+        let bank = self.rom_bank as u16 % self.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        if !(self.ram_enable_1 && self.ram_enable_2) || addr & 0x1000 != 0 {
+            return MappingResult::NotMapped;
+        }
+
+        let value = match (addr >> 4) & 0xF {
+            0x2 => self.x_latch as u8,
+            0x3 => (self.x_latch >> 8) as u8,
+            0x4 => self.y_latch as u8,
+            0x5 => (self.y_latch >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read_pins(),
+            _ => 0xFF,
+        };
+
+        MappingResult::Value(value)
+    }
+
+    fn map_ram_write(&mut self, addr: u16, data: u8) -> MappingResult {
+        if !(self.ram_enable_1 && self.ram_enable_2) || addr & 0x1000 != 0 {
+            return MappingResult::NotMapped;
+        }
+
+        match (addr >> 4) & 0xF {
+            0x0 if data == 0x55 => {
+                self.latch_ready = true;
+                self.x_latch = 0x8000;
+                self.y_latch = 0x8000;
+            }
+            0x1 if data == 0xAA && self.latch_ready => {
+                self.latch_ready = false;
+                self.latch_accelerometer();
+            }
+            0x8 => self.eeprom.write_pins(data),
+            _ => {}
+        }
+
+        MappingResult::NotMapped
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable_1 = data == 0xA,
+            0x2000..=0x3FFF => self.rom_bank = data,
+            0x4000..=0x5FFF => self.ram_enable_2 = data == 0x40,
+            _ => {}
+        }
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        self.eeprom.data.clone()
+    }
+
+    fn load_battery(&mut self, data: &[u8]) -> bool {
+        // some emulators only save the 256 bytes of the 93LC56
+        if data.len() != EEPROM_SIZE && data.len() != EEPROM_SIZE / 2 {
+            return false;
+        }
+
+        self.eeprom.data[..data.len()].copy_from_slice(data);
+        true
+    }
+
+    fn take_battery_written(&mut self) -> bool {
+        std::mem::replace(&mut self.eeprom.written, false)
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(Mbc7, 0x300);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_bits(eeprom: &mut Eeprom, value: u32, bits: u8) {
+        for i in (0..bits).rev() {
+            let di = ((value >> i) & 1) as u8;
+            eeprom.write_pins(0x80 | di << 1);
+            eeprom.write_pins(0xC0 | di << 1);
+        }
+    }
+
+    /// Sends the start bit, the opcode and the address
+    fn send_command(eeprom: &mut Eeprom, opcode: u32, addr: u32) {
+        send_bits(eeprom, 1 << 10 | opcode << 8 | addr, 11);
+    }
+
+    fn read_bits(eeprom: &mut Eeprom, bits: u8) -> u32 {
+        let mut value = 0;
+        for _ in 0..bits {
+            eeprom.write_pins(0x80);
+            eeprom.write_pins(0xC0);
+            value = value << 1 | (eeprom.read_pins() & 1) as u32;
+        }
+        value
+    }
+
+    fn select(eeprom: &mut Eeprom) {
+        eeprom.write_pins(0x00);
+        eeprom.write_pins(0x80);
+    }
+
+    #[test]
+    fn eeprom_write_and_read() {
+        let mut eeprom = Eeprom::new();
+
+        // write before EWEN is ignored
+        select(&mut eeprom);
+        send_command(&mut eeprom, 0b01, 5);
+        send_bits(&mut eeprom, 0x1234, 16);
+        assert_eq!(eeprom.read_word(5), 0xFFFF);
+
+        // EWEN
+        select(&mut eeprom);
+        send_command(&mut eeprom, 0b00, 0xC0);
+
+        select(&mut eeprom);
+        send_command(&mut eeprom, 0b01, 5);
+        send_bits(&mut eeprom, 0x1234, 16);
+        assert_eq!(eeprom.read_word(5), 0x1234);
+        assert_eq!(&eeprom.data[10..12], &[0x34, 0x12]);
+
+        // READ, with the dummy bit then sequential words
+        select(&mut eeprom);
+        send_command(&mut eeprom, 0b10, 4);
+        assert_eq!(eeprom.read_pins() & 1, 0);
+        assert_eq!(read_bits(&mut eeprom, 32), 0xFFFF_1234);
+
+        // ERASE
+        select(&mut eeprom);
+        send_command(&mut eeprom, 0b11, 5);
+        assert_eq!(eeprom.read_word(5), 0xFFFF);
+    }
+
+    #[test]
+    fn accelerometer_latch() {
+        let mut mbc7 = Mbc7::default();
+        mbc7.init(64, 0);
+        mbc7.write_bank_controller_register(0x0000, 0x0A);
+        mbc7.write_bank_controller_register(0x4000, 0x40);
+
+        let read = |mbc7: &mut Mbc7, addr| match mbc7.map_ram_read(addr) {
+            MappingResult::Value(value) => value,
+            _ => panic!("registers should return values"),
+        };
+
+        mbc7.set_tilt(0., 1.);
+        mbc7.map_ram_write(0xA000, 0x55);
+        assert_eq!(read(&mut mbc7, 0xA020), 0x00);
+        assert_eq!(read(&mut mbc7, 0xA030), 0x80);
+
+        mbc7.map_ram_write(0xA010, 0xAA);
+        assert_eq!(read(&mut mbc7, 0xA020), 0xD0);
+        assert_eq!(read(&mut mbc7, 0xA030), 0x81);
+        assert_eq!(read(&mut mbc7, 0xA040), 0x40);
+        assert_eq!(read(&mut mbc7, 0xA050), 0x82);
+
+        // latching again requires writing 0x55 first
+        mbc7.set_tilt(0., 0.);
+        mbc7.map_ram_write(0xA010, 0xAA);
+        assert_eq!(read(&mut mbc7, 0xA040), 0x40);
+    }
+}
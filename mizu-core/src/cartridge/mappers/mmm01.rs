@@ -0,0 +1,219 @@
+use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
+
+/// MMM01 multicart, it boots into a menu in the last 32KB of the ROM, the
+/// menu then sets the base bank and the size of the selected game and locks
+/// them by enabling the mapping, after that the mapper acts like MBC1 inside
+/// the game's part of the ROM.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Mmm01 {
+    rom_banks: u16,
+    ram_banks: u8,
+
+    /// before mapping, the menu is mapped and all registers are writable
+    mapped: bool,
+    ram_enable: bool,
+
+    /// RB0-RB8
+    rom_bank: u16,
+    /// RA0-RA3
+    ram_bank: u8,
+
+    /// bits of RB1-RB4 that cannot be changed by the game after mapping
+    rom_bank_mask: u8,
+    /// bits of RA0-RA1 that cannot be changed by the game after mapping
+    ram_bank_mask: u8,
+
+    /// true for ram banking mode (MBC1 mode 1)
+    mode: bool,
+    mode_write_disable: bool,
+    /// RA0-RA1 are used as RB5-RB6 and the opposite, used for games bigger
+    /// than 512KB (like MBC1)
+    multiplex: bool,
+}
+
+impl Mmm01 {
+    /// The bits of RB0-RB4 that the game can change after mapping
+    #[inline]
+    fn rom_bank_game_bits(&self) -> u16 {
+        0x1F & !((self.rom_bank_mask as u16) << 1)
+    }
+
+    /// Applies the multiplexing to RB and RA, returns `(rom_bank, ram_bank)`
+    fn banks(&self) -> (u16, u8) {
+        if self.multiplex {
+            let rom_bank = (self.rom_bank & !0x60) | ((self.ram_bank as u16 & 0x3) << 5);
+            let ram_bank = (self.ram_bank & !0x3) | ((self.rom_bank >> 5) as u8 & 0x3);
+            (rom_bank, ram_bank)
+        } else {
+            (self.rom_bank, self.ram_bank)
+        }
+    }
+}
+
+impl Mapper for Mmm01 {
+    fn init(&mut self, rom_banks: u16, ram_size: usize) {
+        assert!(rom_banks <= 512);
+        self.rom_banks = rom_banks;
+        self.ram_banks = (ram_size / 0x2000) as u8;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        let bank = if self.mapped {
+            let (rom_bank, _) = self.banks();
+            // the game's bits are cleared, leaving the base of the game
+            let mut bank = rom_bank & !self.rom_bank_game_bits();
+            if !self.mode && self.multiplex {
+                bank &= !0x60;
+            }
+            bank
+        } else {
+            // the menu is in the last 32KB
+            0x1FE
+        } % self.rom_banks;
+
+        bank as usize * 0x4000 + addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        let addr = addr & 0x3FFF;
+
+        let bank = if self.mapped {
+            let (mut bank, _) = self.banks();
+            // same as MBC1, bank 0 of the game cannot be mapped here
+            if bank & self.rom_bank_game_bits() == 0 {
+                bank |= 1;
+            }
+            bank
+        } else {
+            0x1FF
+        } % self.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        if !self.ram_enable || self.ram_banks == 0 {
+            return MappingResult::NotMapped;
+        }
+
+        let (_, mut bank) = self.banks();
+        if !self.mode {
+            bank &= !0x3;
+        }
+        let bank = bank % self.ram_banks;
+
+        MappingResult::Addr(bank as usize * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+
+    fn map_ram_write(&mut self, addr: u16, _data: u8) -> MappingResult {
+        self.map_ram_read(addr)
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enable = data & 0xF == 0xA;
+
+                if !self.mapped {
+                    self.ram_bank_mask = (data >> 4) & 0x3;
+                    self.mapped = data & 0x40 != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let writable = if self.mapped {
+                    self.rom_bank_game_bits()
+                } else {
+                    // RB5-RB6 are only writable before mapping
+                    0x7F
+                };
+
+                self.rom_bank = (self.rom_bank & !writable) | (data as u16 & writable);
+            }
+            0x4000..=0x5FFF => {
+                let writable = if self.mapped {
+                    0x3 & !self.ram_bank_mask
+                } else {
+                    0xF
+                };
+                self.ram_bank = (self.ram_bank & !writable) | (data & writable);
+
+                if !self.mapped {
+                    // RB7-RB8
+                    self.rom_bank = (self.rom_bank & 0x7F) | (((data as u16 >> 4) & 0x3) << 7);
+                    self.mode_write_disable = data & 0x40 != 0;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.mode_write_disable {
+                    self.mode = data & 1 != 0;
+                }
+
+                if !self.mapped {
+                    self.rom_bank_mask = (data >> 2) & 0xF;
+                    self.multiplex = data & 0x40 != 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(Mmm01, 32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn romx_bank(mmm01: &Mmm01) -> usize {
+        match mmm01.map_read_romx(0x4000) {
+            MappingResult::Addr(addr) => addr / 0x4000,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn boots_into_menu() {
+        let mut mmm01 = Mmm01::default();
+        mmm01.init(64, 0);
+
+        assert_eq!(mmm01.map_read_rom0(0) / 0x4000, 62);
+        assert_eq!(romx_bank(&mmm01), 63);
+    }
+
+    #[test]
+    fn game_is_locked_after_mapping() {
+        let mut mmm01 = Mmm01::default();
+        mmm01.init(64, 0);
+
+        // a 128KB game (8 banks) starting at bank 16
+        mmm01.write_bank_controller_register(0x2000, 16);
+        // only RB0-RB2 are controlled by the game
+        mmm01.write_bank_controller_register(0x6000, 0b1100 << 2);
+        mmm01.write_bank_controller_register(0x0000, 0x40);
+
+        assert_eq!(mmm01.map_read_rom0(0) / 0x4000, 16);
+        assert_eq!(romx_bank(&mmm01), 17);
+
+        mmm01.write_bank_controller_register(0x2000, 5);
+        assert_eq!(romx_bank(&mmm01), 21);
+
+        // bank 8 of the game wraps to bank 0, which is mapped as 1
+        mmm01.write_bank_controller_register(0x2000, 8);
+        assert_eq!(romx_bank(&mmm01), 17);
+
+        // the mask cannot be changed after mapping
+        mmm01.write_bank_controller_register(0x6000, 0);
+        mmm01.write_bank_controller_register(0x2000, 0x1F);
+        assert_eq!(romx_bank(&mmm01), 23);
+    }
+}
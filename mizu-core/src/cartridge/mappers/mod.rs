@@ -1,28 +1,81 @@
+mod huc1;
+mod huc3;
 mod mbc1;
 mod mbc2;
 mod mbc3;
 mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
 mod no_mapper;
+mod pocket_camera;
+mod sachen;
+mod tama5;
+mod wisdom_tree;
 
+pub(super) use huc1::Huc1;
+pub(super) use huc3::Huc3;
 pub(super) use mbc1::Mbc1;
 pub(super) use mbc2::Mbc2;
-pub(super) use mbc3::Mbc3;
+pub(super) use mbc3::{Mbc3, Mbc3Clone};
 pub(super) use mbc5::Mbc5;
+pub(super) use mbc6::Mbc6;
+pub(super) use mbc7::Mbc7;
+pub(super) use mmm01::Mmm01;
 pub(super) use no_mapper::NoMapper;
+pub(super) use pocket_camera::PocketCamera;
+pub(super) use sachen::{unscramble_address, Sachen};
+pub(super) use tama5::Tama5;
+pub(super) use wisdom_tree::WisdomTree;
+
+pub use pocket_camera::{CAMERA_HEIGHT, CAMERA_WIDTH};
+
+use crate::infrared::InfraredDevice;
+use crate::save_state::SaveError;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// The number of clocks needed from the bus to complete one second
 pub const ONE_SECOND_MAPPER_CLOCKS: u32 = 4194304 / 2;
 
-#[derive(Debug, Clone, Copy)]
+/// The current UNIX timestamp in seconds, used by the RTC mappers
+fn system_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|e| e.duration())
+        .as_secs()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MapperType {
     NoMapper,
-    Mbc1 { multicart: bool },
+    Mbc1 {
+        multicart: bool,
+    },
     Mbc2,
-    Mbc3 { timer: bool },
-    Mbc5 { rumble: bool },
+    Mbc3 {
+        timer: bool,
+    },
+    Mbc5 {
+        rumble: bool,
+    },
     Mmm01,
     Mbc6,
     Mbc7,
+    Huc1,
+    Huc3,
+    PocketCamera,
+    Tama5,
+    /// Unlicensed mapper of Wisdom Tree games
+    WisdomTree,
+    /// Unlicensed mapper of Sachen games
+    SachenMmc1,
+    /// Unlicensed mapper of Sachen GBC games
+    SachenMmc2,
+    /// Unlicensed MBC3 clone with protection registers, used in games like
+    /// Pokemon Jade and Diamond
+    Mbc3Clone,
 }
 
 pub enum MappingResult {
@@ -36,7 +89,7 @@ pub trait Mapper {
 
     fn map_read_rom0(&self, addr: u16) -> usize;
 
-    fn map_read_romx(&self, addr: u16) -> usize;
+    fn map_read_romx(&self, addr: u16) -> MappingResult;
 
     fn map_ram_read(&mut self, addr: u16) -> MappingResult;
 
@@ -46,23 +99,90 @@ pub trait Mapper {
         // ignored
     }
 
-    fn save_battery_size(&self) -> usize {
-        0
+    /// Called before every read of the CPU from `0x0000-0x7FFF`, but not for
+    /// reads done for debugging, for mappers that change their state on
+    /// reads. The read may be from the boot ROM.
+    fn on_cpu_read(&mut self, _addr: u16) {
+        // ignored
+    }
+
+    /// Puts the mapper in the state it would be in after the boot ROM, when
+    /// the emulation starts without it
+    fn skip_boot_rom(&mut self) {
+        // ignored
     }
 
     fn save_battery(&self) -> Vec<u8> {
         Vec::new()
     }
 
-    fn load_battery(&mut self, _data: &[u8]) {
+    /// Loads the extra data after the SRAM in the save file, which may come
+    /// from other emulators, returns `false` if the data is not in a known
+    /// format
+    fn load_battery(&mut self, _data: &[u8]) -> bool {
+        // ignored
+        true
+    }
+
+    /// Returns `true` if battery backed data inside the mapper (not in the
+    /// cartridge RAM) was modified since the last call, so it can be saved
+    fn take_battery_written(&mut self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the rumble motor of the cartridge is on
+    fn rumble_active(&self) -> bool {
+        false
+    }
+
+    /// Returns the number of clocks the rumble motor was on since the last
+    /// call
+    fn take_rumble_cycles(&mut self) -> u32 {
+        0
+    }
+
+    /// Sets the tilt of the cartridge for mappers with an accelerometer,
+    /// in units of g
+    fn set_tilt(&mut self, _x: f32, _y: f32) {
+        // ignored
+    }
+
+    /// Connects the device to the infrared port of the cartridge, if it has one
+    fn connect_infrared_device(&mut self, _device: Option<Rc<RefCell<dyn InfraredDevice>>>) {
+        // ignored
+    }
+
+    /// Sets the image in front of the camera sensor, for mappers with a
+    /// camera, `frame` is `CAMERA_WIDTH * CAMERA_HEIGHT` greyscale pixels
+    fn set_camera_frame(&mut self, _frame: &[u8]) {
         // ignored
     }
 
+    /// Saves the internal state of the mapper (bank registers, ram enable, ...)
+    /// to be included in save states
+    fn save_state(&self) -> Result<Vec<u8>, SaveError>;
+
+    /// Loads the internal state saved by [`save_state`](Self::save_state)
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError>;
+
+    /// The flash memory inside the mapper (MBC6), which is saved in its own
+    /// section of save states, as it is too large for the mapper state
+    fn flash(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn flash_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
     /// Fixed-timed updates from the bus, the main purpose is to be used to
     /// sync the MBC3 RTC clock to emulation in case emulation speed changed
     ///
     /// the number of clocks for one second is (ONE_SECOND_MAPPER_CLOCKS) 4194304 / 2
-    fn clock(&mut self) {
-        // ignore
+    ///
+    /// `ram` is the cartridge RAM, for mappers that write to it by themselves
+    /// (like the camera), returns `true` if it was modified
+    fn clock(&mut self, _ram: &mut [u8]) -> bool {
+        false
     }
 }
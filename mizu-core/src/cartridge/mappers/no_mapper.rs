@@ -1,5 +1,8 @@
 use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize)]
 pub struct NoMapper {
     ram_size: usize,
 }
@@ -23,9 +26,9 @@ impl Mapper for NoMapper {
         addr as usize
     }
 
-    fn map_read_romx(&self, addr: u16) -> usize {
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
         // return the same address
-        addr as usize
+        MappingResult::Addr(addr as usize)
     }
 
     fn map_ram_read(&mut self, addr: u16) -> MappingResult {
@@ -39,4 +42,14 @@ impl Mapper for NoMapper {
     fn map_ram_write(&mut self, addr: u16, _data: u8) -> MappingResult {
         self.map_ram_read(addr)
     }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
 }
+
+impl_savable!(NoMapper, 32);
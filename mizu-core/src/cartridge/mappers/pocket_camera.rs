@@ -0,0 +1,376 @@
+use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
+
+pub const CAMERA_WIDTH: usize = 128;
+pub const CAMERA_HEIGHT: usize = 112;
+
+/// The captured image is saved in RAM bank 0 after this offset, in the tile
+/// format (16x14 tiles)
+const IMAGE_RAM_OFFSET: usize = 0x100;
+
+/// Edge enhancement ratios selected by bits 4-6 of `A004`
+const EDGE_RATIOS: [f32; 8] = [0.50, 0.75, 1.00, 1.25, 2.00, 3.00, 4.00, 5.00];
+
+/// Game Boy Camera (Pocket Camera), has the M64282FP image sensor which is
+/// controlled by registers mapped in `0xA000-0xBFFF` when RAM bank `0x10`
+/// is selected.
+///
+/// The exposure, the gain, the invert bit, the 2D edge enhancement (mode
+/// `0xE0` of `A001`) and the dithering matrix are emulated. The other edge
+/// modes, the output reference voltage (`A004` bits 0-2) and the offset
+/// calibration (`A005`) are not, the output is the same as without them.
+#[derive(Serialize, Deserialize)]
+pub struct PocketCamera {
+    rom_banks: u16,
+    ram_banks: u8,
+
+    ram_enable: bool,
+    rom_bank: u8,
+    /// bit 4 maps the camera registers instead of the RAM
+    ram_bank: u8,
+
+    /// - `A000`: bit 0 starts the capture and stays `1` while capturing
+    /// - `A001`: bits 0-4 gain, bits 5-7 edge enhancement mode (N, VH)
+    /// - `A002-A003`: exposure time (big endian)
+    /// - `A004`: bits 0-2 output reference voltage, bit 3 invert,
+    ///   bits 4-6 edge enhancement ratio
+    /// - `A005`: output voltage offset and zero point calibration
+    registers: [u8; 6],
+    /// `A006-A035`: 4x4 matrix of 3 thresholds each, used to convert the
+    /// sensor output into the 4 shades of the gameboy
+    dither_matrix: Vec<u8>,
+
+    /// the remaining mapper clocks until the capture is done
+    capture_clocks: u32,
+
+    /// the image in front of the sensor, one byte per pixel from `0` (black)
+    /// to `255` (white)
+    #[serde(skip)]
+    frame: Vec<u8>,
+}
+
+impl Default for PocketCamera {
+    fn default() -> Self {
+        Self {
+            rom_banks: 0,
+            ram_banks: 0,
+            ram_enable: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0; 6],
+            dither_matrix: vec![0; 0x30],
+            capture_clocks: 0,
+            frame: vec![0; CAMERA_WIDTH * CAMERA_HEIGHT],
+        }
+    }
+}
+
+impl PocketCamera {
+    #[inline]
+    fn registers_mapped(&self) -> bool {
+        self.ram_bank & 0x10 != 0
+    }
+
+    #[inline]
+    fn exposure(&self) -> u16 {
+        (self.registers[2] as u16) << 8 | self.registers[3] as u16
+    }
+
+    fn map_ram(&self, addr: u16) -> MappingResult {
+        if self.ram_banks == 0 {
+            return MappingResult::NotMapped;
+        }
+
+        let bank = (self.ram_bank & 0xF) % self.ram_banks;
+        MappingResult::Addr(bank as usize * 0x2000 + (addr & 0x1FFF) as usize)
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr & 0x7F {
+            0x00 => {
+                let start = data & 1 != 0 && self.capture_clocks == 0;
+                self.registers[0] = data & 0x7;
+
+                if start {
+                    self.start_capture();
+                } else if data & 1 == 0 {
+                    // stops the capture
+                    self.capture_clocks = 0;
+                }
+            }
+            reg @ 0x01..=0x05 => self.registers[reg as usize] = data,
+            reg @ 0x06..=0x35 => self.dither_matrix[reg as usize - 0x06] = data,
+            _ => {}
+        }
+    }
+
+    fn start_capture(&mut self) {
+        // in CPU cycles at 1MHz, the N bit skips some of the processing
+        let n = self.registers[1] & 0x80 != 0;
+        let cycles = 32446 + if n { 0 } else { 512 } + 16 * self.exposure() as u32;
+
+        // the mapper is clocked at 2MHz
+        self.capture_clocks = cycles * 2;
+    }
+
+    /// The output of the sensor for a pixel after the exposure and gain,
+    /// pixels outside the image are taken from the nearest edge
+    fn sensor_output(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, CAMERA_WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, CAMERA_HEIGHT as isize - 1) as usize;
+        let pixel = self.frame[y * CAMERA_WIDTH + x] as f32;
+
+        // the gain is in steps of 1.5dB
+        let gain = 10f32.powf((self.registers[1] & 0x1F) as f32 * 1.5 / 20.);
+
+        pixel * gain * self.exposure() as f32 / 0x1000 as f32
+    }
+
+    /// Processes the frame and writes it to RAM as 2bpp tiles
+    fn capture(&self, ram: &mut [u8]) {
+        let image_ram = match ram.get_mut(IMAGE_RAM_OFFSET..IMAGE_RAM_OFFSET + 0xE00) {
+            Some(image_ram) => image_ram,
+            None => return,
+        };
+
+        // only the 2D edge enhancement mode is emulated
+        let edge_enhancement = self.registers[1] & 0xE0 == 0xE0;
+        let edge_ratio = EDGE_RATIOS[(self.registers[4] as usize >> 4) & 0x7];
+        let invert = self.registers[4] & 0x08 != 0;
+
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let (ix, iy) = (x as isize, y as isize);
+                let mut value = self.sensor_output(ix, iy);
+
+                if edge_enhancement {
+                    let neighbours = self.sensor_output(ix - 1, iy)
+                        + self.sensor_output(ix + 1, iy)
+                        + self.sensor_output(ix, iy - 1)
+                        + self.sensor_output(ix, iy + 1);
+
+                    value += (value * 4. - neighbours) * edge_ratio;
+                }
+
+                if invert {
+                    value = 255. - value.clamp(0., 255.);
+                }
+
+                let matrix_index = ((y % 4) * 4 + x % 4) * 3;
+                let thresholds = &self.dither_matrix[matrix_index..matrix_index + 3];
+
+                let color = if value < thresholds[0] as f32 {
+                    3
+                } else if value < thresholds[1] as f32 {
+                    2
+                } else if value < thresholds[2] as f32 {
+                    1
+                } else {
+                    0
+                };
+
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + x / 8;
+                let offset = tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+
+                image_ram[offset] &= !(1 << bit);
+                image_ram[offset] |= (color & 1) << bit;
+                image_ram[offset + 1] &= !(1 << bit);
+                image_ram[offset + 1] |= (color >> 1) << bit;
+            }
+        }
+    }
+}
+
+impl Mapper for PocketCamera {
+    fn init(&mut self, rom_banks: u16, ram_size: usize) {
+        self.rom_banks = rom_banks;
+        self.ram_banks = (ram_size / 0x2000) as u8;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        let addr = addr & 0x3FFF;
+
+        // bank 0 can be mapped here
+        let bank = self.rom_bank as u16 % self.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        if self.registers_mapped() {
+            // only `A000` can be read
+            if addr & 0x7F == 0 {
+                MappingResult::Value(self.registers[0])
+            } else {
+                MappingResult::Value(0)
+            }
+        } else if self.capture_clocks != 0 {
+            // the RAM is used by the sensor while capturing
+            MappingResult::Value(0)
+        } else {
+            // reading is allowed even if the RAM is disabled
+            self.map_ram(addr)
+        }
+    }
+
+    fn map_ram_write(&mut self, addr: u16, data: u8) -> MappingResult {
+        if self.registers_mapped() {
+            self.write_register(addr, data);
+            MappingResult::NotMapped
+        } else if !self.ram_enable || self.capture_clocks != 0 {
+            MappingResult::NotMapped
+        } else {
+            self.map_ram(addr)
+        }
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable = data & 0xF == 0xA,
+            0x2000..=0x3FFF => self.rom_bank = data & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = data & 0x1F,
+            _ => {}
+        }
+    }
+
+    fn set_camera_frame(&mut self, frame: &[u8]) {
+        self.frame.copy_from_slice(frame);
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        // the frame is not part of the state
+        let frame = std::mem::take(&mut self.frame);
+        let result = load_object(self, data);
+        self.frame = frame;
+
+        result
+    }
+
+    fn clock(&mut self, ram: &mut [u8]) -> bool {
+        if self.capture_clocks != 0 {
+            self.capture_clocks -= 1;
+
+            if self.capture_clocks == 0 {
+                self.capture(ram);
+                self.registers[0] &= !1;
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl_savable!(PocketCamera, 128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_value(camera: &mut PocketCamera, addr: u16) -> Option<u8> {
+        match camera.map_ram_read(addr) {
+            MappingResult::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn capture(camera: &mut PocketCamera, ram: &mut [u8]) {
+        camera.write_bank_controller_register(0x4000, 0x10);
+        camera.map_ram_write(0xA000, 0x01);
+        assert_eq!(read_value(camera, 0xA000), Some(1));
+
+        while !camera.clock(ram) {}
+        assert_eq!(read_value(camera, 0xA000), Some(0));
+    }
+
+    /// A camera with an exposure of 1.0, no gain, and evenly spaced
+    /// thresholds, in front of an image with a black left half and a white
+    /// right half
+    fn setup() -> PocketCamera {
+        let mut camera = PocketCamera::default();
+        camera.init(64, 0x20000);
+
+        camera.write_bank_controller_register(0x4000, 0x10);
+        camera.map_ram_write(0xA002, 0x10);
+        camera.map_ram_write(0xA003, 0x00);
+        for i in 0..16 {
+            camera.map_ram_write(0xA006 + i * 3, 0x40);
+            camera.map_ram_write(0xA007 + i * 3, 0x80);
+            camera.map_ram_write(0xA008 + i * 3, 0xC0);
+        }
+
+        let frame = (0..CAMERA_WIDTH * CAMERA_HEIGHT)
+            .map(|i| if i % CAMERA_WIDTH < 64 { 0 } else { 255 })
+            .collect::<Vec<_>>();
+        camera.set_camera_frame(&frame);
+
+        camera
+    }
+
+    #[test]
+    fn capture_and_dither() {
+        let mut camera = setup();
+        let mut ram = vec![0; 0x20000];
+
+        capture(&mut camera, &mut ram);
+
+        // the first tile is black (3), and the last tile is white (0)
+        assert_eq!(&ram[0x100..0x110], &[0xFF; 16]);
+        assert_eq!(&ram[0xF00 - 0x10..0xF00], &[0; 16]);
+    }
+
+    #[test]
+    fn invert() {
+        let mut camera = setup();
+        let mut ram = vec![0; 0x20000];
+
+        camera.map_ram_write(0xA004, 0x08);
+        capture(&mut camera, &mut ram);
+
+        assert_eq!(&ram[0x100..0x110], &[0; 16]);
+        assert_eq!(&ram[0xF00 - 0x10..0xF00], &[0xFF; 16]);
+    }
+
+    #[test]
+    fn unemulated_settings_are_ignored() {
+        let mut camera = setup();
+        let mut expected = vec![0; 0x20000];
+        capture(&mut camera, &mut expected);
+
+        // edge enhancement in 1D mode, reference voltage and offset
+        camera.map_ram_write(0xA001, 0x60);
+        camera.map_ram_write(0xA004, 0x07);
+        camera.map_ram_write(0xA005, 0x3F);
+        let mut ram = vec![0; 0x20000];
+        capture(&mut camera, &mut ram);
+
+        assert!(ram == expected);
+    }
+
+    #[test]
+    fn ram_is_not_accessible_while_capturing() {
+        let mut camera = PocketCamera::default();
+        camera.init(64, 0x20000);
+
+        camera.write_bank_controller_register(0x0000, 0x0A);
+        camera.write_bank_controller_register(0x4000, 0x10);
+        camera.map_ram_write(0xA000, 0x01);
+        camera.write_bank_controller_register(0x4000, 0x00);
+
+        assert_eq!(read_value(&mut camera, 0xA100), Some(0));
+        assert!(matches!(
+            camera.map_ram_write(0xA100, 0),
+            MappingResult::NotMapped
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
+
+/// The number of header reads until the lock is released, the boot ROM
+/// reads the logo in the first `0x30` reads
+const LOCK_READS: u8 = 0x31;
+
+/// The address lines `A0` and `A6`, and `A1` and `A4` are swapped for reads
+/// in `0x0100-0x01FF`, so the header in the ROM file is scrambled
+pub fn unscramble_address(addr: u16) -> u16 {
+    if addr & 0xFF00 != 0x0100 {
+        return addr;
+    }
+
+    (addr & 0xFFAC)
+        | (addr & 0x40) >> 6
+        | (addr & 0x10) >> 3
+        | (addr & 0x02) << 3
+        | (addr & 0x01) << 6
+}
+
+/// Sachen's unlicensed MMC1 and MMC2 mappers.
+///
+/// Their ROM has Sachen's logo in the header, while the Nintendo logo is
+/// placed at `0x0184`. While locked, reads from `0x0100-0x01FF` have `A7`
+/// set, so the boot ROM sees the Nintendo logo, then the cartridge unlocks
+/// after `0x30` reads (MMC1), or after writing to `0x6000-0x7FFF` (MMC2).
+///
+/// Without the boot ROM, the cartridge starts unlocked.
+#[derive(Serialize, Deserialize)]
+pub struct Sachen {
+    rom_banks: u16,
+    /// MMC2 is used in GBC games, it has an extra lock stage that is released
+    /// by reads outside the cartridge done by the CGB boot ROM, which the
+    /// mapper can't see, so it starts at the second stage
+    mmc2: bool,
+
+    base_bank: u8,
+    bank_mask: u8,
+    rom_bank: u8,
+
+    locked: bool,
+    /// the number of CPU reads from `0x0100-0x01FF` while locked
+    lock_reads: u8,
+}
+
+impl Sachen {
+    pub fn new(mmc2: bool) -> Self {
+        Self {
+            rom_banks: 0,
+            mmc2,
+            base_bank: 0,
+            bank_mask: 0,
+            rom_bank: 1,
+            locked: true,
+            lock_reads: 0,
+        }
+    }
+
+    /// The base bank and the mask can only be changed if bits 4 and 5 of the
+    /// ROM bank are set
+    #[inline]
+    fn base_writable(&self) -> bool {
+        self.rom_bank & 0x30 == 0x30
+    }
+}
+
+impl Mapper for Sachen {
+    fn init(&mut self, rom_banks: u16, _ram_size: usize) {
+        self.rom_banks = rom_banks;
+    }
+
+    fn map_read_rom0(&self, mut addr: u16) -> usize {
+        if addr & 0xFF00 == 0x0100 && self.locked {
+            addr |= 0x80;
+        }
+
+        let bank = (self.base_bank & self.bank_mask) as u16 % self.rom_banks;
+
+        bank as usize * 0x4000 + unscramble_address(addr) as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        let addr = addr & 0x3FFF;
+
+        let bank = (self.rom_bank & !self.bank_mask) | (self.base_bank & self.bank_mask);
+        let bank = bank as u16 % self.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
+    }
+
+    fn on_cpu_read(&mut self, addr: u16) {
+        if addr & 0xFF00 != 0x0100 || !self.locked {
+            return;
+        }
+
+        self.lock_reads += 1;
+        if self.lock_reads == LOCK_READS {
+            self.lock_reads = 0;
+            // MMC2 stays locked until it is unlocked by a write
+            if !self.mmc2 {
+                self.locked = false;
+            }
+        }
+    }
+
+    fn skip_boot_rom(&mut self) {
+        self.locked = false;
+        self.lock_reads = 0;
+    }
+
+    fn map_ram_read(&mut self, _addr: u16) -> MappingResult {
+        MappingResult::NotMapped
+    }
+
+    fn map_ram_write(&mut self, _addr: u16, _data: u8) -> MappingResult {
+        MappingResult::NotMapped
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x0000..=0x1FFF if self.base_writable() => self.base_bank = data,
+            0x2000..=0x3FFF => {
+                self.rom_bank = data;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF if self.base_writable() => self.bank_mask = data,
+            0x6000..=0x7FFF if self.mmc2 && data & 0x30 == 0x10 => {
+                self.locked = false;
+                self.lock_reads = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(Sachen, 16);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logo_is_redirected_while_locked() {
+        let mut sachen = Sachen::new(false);
+        sachen.init(8, 0);
+
+        // the boot ROM reads the logo from 0x0184 (scrambled)
+        for i in 0..0x30 {
+            let addr = 0x104 + i;
+            let expected = unscramble_address(addr | 0x80) as usize;
+            sachen.on_cpu_read(addr);
+            assert_eq!(sachen.map_read_rom0(addr), expected);
+        }
+
+        // reads for debugging do not count
+        assert_eq!(
+            sachen.map_read_rom0(0x134),
+            unscramble_address(0x134 | 0x80) as usize
+        );
+
+        // unlocked after 0x31 reads
+        sachen.on_cpu_read(0x134);
+        assert_eq!(
+            sachen.map_read_rom0(0x134),
+            unscramble_address(0x134) as usize
+        );
+        assert_eq!(
+            sachen.map_read_rom0(0x104),
+            unscramble_address(0x104) as usize
+        );
+        assert_eq!(sachen.map_read_rom0(0x200), 0x200);
+    }
+
+    #[test]
+    fn unlocked_without_boot_rom() {
+        let mut sachen = Sachen::new(false);
+        sachen.init(8, 0);
+        sachen.skip_boot_rom();
+
+        sachen.on_cpu_read(0x100);
+        assert_eq!(
+            sachen.map_read_rom0(0x100),
+            unscramble_address(0x100) as usize
+        );
+    }
+
+    #[test]
+    fn base_bank_and_mask() {
+        let mut sachen = Sachen::new(false);
+        sachen.init(64, 0);
+
+        // base 0x20, the upper bits are from the base
+        sachen.write_bank_controller_register(0x2000, 0x30);
+        sachen.write_bank_controller_register(0x0000, 0x20);
+        sachen.write_bank_controller_register(0x4000, 0x30);
+        sachen.write_bank_controller_register(0x2000, 0x05);
+
+        assert_eq!(sachen.map_read_rom0(0) / 0x4000, 0x20);
+        assert!(matches!(
+            sachen.map_read_romx(0x4000),
+            MappingResult::Addr(addr) if addr / 0x4000 == 0x25
+        ));
+    }
+}
@@ -0,0 +1,468 @@
+use super::{system_time_now, Mapper, MappingResult, ONE_SECOND_MAPPER_CLOCKS};
+use crate::save_state::{load_object, save_object, SaveError};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// The size of the RAM inside the TAMA6 microcontroller
+const RAM_SIZE: usize = 0x20;
+
+/// The RTC footer after the RAM in the `.sav` file:
+/// - the UNIX timestamp of the time the file was saved (64bit little endian).
+/// - seconds, minutes, hours, day of the week, day, month, year, alarm
+///   minutes, alarm hours and alarm flags (8bit each).
+const RTC_FOOTER_SIZE: usize = 18;
+
+/// The registers selected by writing to `0xA001`
+mod register {
+    pub const ROM_BANK_LOW: u8 = 0x0;
+    pub const ROM_BANK_HIGH: u8 = 0x1;
+    pub const DATA_LOW: u8 = 0x4;
+    pub const DATA_HIGH: u8 = 0x5;
+    /// bit 0 is bit 4 of the address, bits 1-3 are the command
+    pub const ADDR_HIGH: u8 = 0x6;
+    /// writing to this executes the command
+    pub const ADDR_LOW: u8 = 0x7;
+    pub const READY: u8 = 0xA;
+    pub const RESULT_LOW: u8 = 0xC;
+    pub const RESULT_HIGH: u8 = 0xD;
+}
+
+/// The part of an RTC register accessed by a nibble register
+enum Nibble {
+    Ones,
+    Tens,
+    Whole,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Tama5Rtc {
+    /// A full second is ONE_SECOND_MAPPER_CLOCKS, which is synced to the bus
+    sub_second: u32,
+
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// 0-6
+    day_of_week: u8,
+    /// 1-31
+    day: u8,
+    /// 1-12
+    month: u8,
+    /// 0-99, leap years are the ones divisible by 4
+    year: u8,
+
+    alarm_minutes: u8,
+    alarm_hours: u8,
+    /// bit 0 enables the alarm, bit 1 is set when the alarm goes off
+    alarm_flags: u8,
+}
+
+impl Default for Tama5Rtc {
+    fn default() -> Self {
+        Self {
+            sub_second: 0,
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            day_of_week: 0,
+            day: 1,
+            month: 1,
+            year: 0,
+            alarm_minutes: 0,
+            alarm_hours: 0,
+            alarm_flags: 0,
+        }
+    }
+}
+
+impl Tama5Rtc {
+    fn days_in_month(&self) -> u8 {
+        match self.month {
+            2 if self.year.is_multiple_of(4) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    fn add_day(&mut self) {
+        self.day_of_week = (self.day_of_week + 1) % 7;
+        self.day += 1;
+
+        if self.day > self.days_in_month() {
+            self.day = 1;
+            self.month += 1;
+
+            if self.month > 12 {
+                self.month = 1;
+                self.year = (self.year + 1) % 100;
+            }
+        }
+    }
+
+    fn add_seconds(&mut self, seconds: u64) {
+        let seconds = self.seconds as u64 + seconds;
+        self.seconds = (seconds % 60) as u8;
+
+        let minutes = self.minutes as u64 + seconds / 60;
+        self.minutes = (minutes % 60) as u8;
+
+        let hours = self.hours as u64 + minutes / 60;
+        self.hours = (hours % 24) as u8;
+
+        for _ in 0..hours / 24 {
+            self.add_day();
+        }
+    }
+
+    fn clock_second_part(&mut self) {
+        self.sub_second += 1;
+
+        if self.sub_second == ONE_SECOND_MAPPER_CLOCKS {
+            self.sub_second = 0;
+            self.add_seconds(1);
+
+            if self.seconds == 0
+                && self.alarm_flags & 1 != 0
+                && self.minutes == self.alarm_minutes
+                && self.hours == self.alarm_hours
+            {
+                self.alarm_flags |= 2;
+            }
+        }
+    }
+
+    /// The registers are nibbles in BCD, `0x00-0x0C` is the time, and
+    /// `0x10-0x1F` is the alarm (in the same layout as the time)
+    fn register_mut(&mut self, index: u8) -> Option<(&mut u8, Nibble)> {
+        use Nibble::*;
+
+        match index & 0x1F {
+            0x00 => Some((&mut self.seconds, Ones)),
+            0x01 => Some((&mut self.seconds, Tens)),
+            0x02 => Some((&mut self.minutes, Ones)),
+            0x03 => Some((&mut self.minutes, Tens)),
+            0x04 => Some((&mut self.hours, Ones)),
+            0x05 => Some((&mut self.hours, Tens)),
+            0x06 => Some((&mut self.day_of_week, Whole)),
+            0x07 => Some((&mut self.day, Ones)),
+            0x08 => Some((&mut self.day, Tens)),
+            0x09 => Some((&mut self.month, Ones)),
+            0x0A => Some((&mut self.month, Tens)),
+            0x0B => Some((&mut self.year, Ones)),
+            0x0C => Some((&mut self.year, Tens)),
+            0x12 => Some((&mut self.alarm_minutes, Ones)),
+            0x13 => Some((&mut self.alarm_minutes, Tens)),
+            0x14 => Some((&mut self.alarm_hours, Ones)),
+            0x15 => Some((&mut self.alarm_hours, Tens)),
+            0x1D => Some((&mut self.alarm_flags, Whole)),
+            _ => None,
+        }
+    }
+
+    fn read_nibble(&mut self, index: u8) -> u8 {
+        match self.register_mut(index) {
+            Some((register, Nibble::Ones)) => *register % 10,
+            Some((register, Nibble::Tens)) => *register / 10,
+            Some((register, Nibble::Whole)) => *register,
+            None => 0,
+        }
+    }
+
+    fn write_nibble(&mut self, index: u8, value: u8) {
+        let value = value & 0xF;
+
+        match self.register_mut(index) {
+            Some((register, Nibble::Ones)) => *register = *register / 10 * 10 + value,
+            Some((register, Nibble::Tens)) => *register = value * 10 + *register % 10,
+            Some((register, Nibble::Whole)) => *register = value,
+            None => {}
+        }
+    }
+
+    fn save_battery(&self, data: &mut Vec<u8>) {
+        data.write_u64::<LittleEndian>(system_time_now()).unwrap();
+        data.extend_from_slice(&[
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_of_week,
+            self.day,
+            self.month,
+            self.year,
+            self.alarm_minutes,
+            self.alarm_hours,
+            self.alarm_flags,
+        ]);
+    }
+
+    fn load_battery(&mut self, data: &[u8]) {
+        let mut cur = Cursor::new(data);
+        let timestamp = cur.read_u64::<LittleEndian>().unwrap();
+
+        for register in &mut [
+            &mut self.seconds,
+            &mut self.minutes,
+            &mut self.hours,
+            &mut self.day_of_week,
+            &mut self.day,
+            &mut self.month,
+            &mut self.year,
+            &mut self.alarm_minutes,
+            &mut self.alarm_hours,
+            &mut self.alarm_flags,
+        ] {
+            **register = cur.read_u8().unwrap();
+        }
+
+        // invalid dates would break the calendar
+        if !(1..=12).contains(&self.month) {
+            self.month = 1;
+        }
+        if !(1..=31).contains(&self.day) {
+            self.day = 1;
+        }
+
+        // the clock keeps running while the game is not played
+        if timestamp != 0 {
+            self.add_seconds(system_time_now().saturating_sub(timestamp));
+        }
+    }
+}
+
+/// Bandai's TAMA5, used in Tamagotchi 3. The game talks to a TAMA6
+/// microcontroller through two registers, `0xA001` selects a register and
+/// `0xA000` reads or writes its value (4 bits).
+///
+/// The microcontroller has 32 bytes of RAM and an RTC with an alarm, which
+/// are accessed with commands (bits 1-3 of [`register::ADDR_HIGH`]):
+/// - `0`: write the data to RAM.
+/// - `1`: read the RAM to the result registers.
+/// - `2`: write the low nibble of the data to an RTC register.
+/// - `3`: read an RTC register to the result registers.
+#[derive(Serialize, Deserialize)]
+pub struct Tama5 {
+    rom_banks: u16,
+
+    selected_register: u8,
+    registers: [u8; 16],
+    /// the result of the last read command
+    result: u8,
+
+    ram: Vec<u8>,
+    ram_written: bool,
+    rtc: Tama5Rtc,
+}
+
+impl Default for Tama5 {
+    fn default() -> Self {
+        Self {
+            rom_banks: 0,
+            selected_register: 0,
+            registers: [0; 16],
+            result: 0,
+            ram: vec![0; RAM_SIZE],
+            ram_written: false,
+            rtc: Tama5Rtc::default(),
+        }
+    }
+}
+
+impl Tama5 {
+    fn execute_command(&mut self) {
+        let addr_high = self.registers[register::ADDR_HIGH as usize];
+        let addr = (addr_high & 1) << 4 | self.registers[register::ADDR_LOW as usize];
+        let data = self.registers[register::DATA_HIGH as usize] << 4
+            | self.registers[register::DATA_LOW as usize];
+
+        match addr_high >> 1 {
+            0 => {
+                self.ram[addr as usize] = data;
+                self.ram_written = true;
+            }
+            1 => self.result = self.ram[addr as usize],
+            2 => {
+                self.rtc.write_nibble(addr, data);
+                self.ram_written = true;
+            }
+            3 => self.result = self.rtc.read_nibble(addr),
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Tama5 {
+    fn init(&mut self, rom_banks: u16, _ram_size: usize) {
+        self.rom_banks = rom_banks;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        let addr = addr & 0x3FFF;
+
+        let bank = (self.registers[register::ROM_BANK_HIGH as usize] as u16 & 1) << 4
+            | self.registers[register::ROM_BANK_LOW as usize] as u16;
+        let bank = bank % self.rom_banks;
+
+        MappingResult::Addr(bank as usize * 0x4000 + addr as usize)
+    }
+
+    fn map_ram_read(&mut self, addr: u16) -> MappingResult {
+        if addr & 1 != 0 {
+            return MappingResult::NotMapped;
+        }
+
+        MappingResult::Value(match self.selected_register {
+            // commands are executed instantly
+            register::READY => 0xF1,
+            register::RESULT_LOW => 0xF0 | self.result & 0xF,
+            register::RESULT_HIGH => 0xF0 | self.result >> 4,
+            _ => 0xFF,
+        })
+    }
+
+    fn map_ram_write(&mut self, addr: u16, data: u8) -> MappingResult {
+        if addr & 1 != 0 {
+            self.selected_register = data & 0xF;
+        } else {
+            self.registers[self.selected_register as usize] = data & 0xF;
+
+            if self.selected_register == register::ADDR_LOW {
+                self.execute_command();
+            }
+        }
+
+        MappingResult::NotMapped
+    }
+
+    fn save_battery(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        self.rtc.save_battery(&mut data);
+
+        data
+    }
+
+    fn load_battery(&mut self, data: &[u8]) -> bool {
+        if data.len() != RAM_SIZE + RTC_FOOTER_SIZE {
+            return false;
+        }
+
+        self.ram.copy_from_slice(&data[..RAM_SIZE]);
+        self.rtc.load_battery(&data[RAM_SIZE..]);
+
+        true
+    }
+
+    fn take_battery_written(&mut self) -> bool {
+        std::mem::replace(&mut self.ram_written, false)
+    }
+
+    fn clock(&mut self, _ram: &mut [u8]) -> bool {
+        self.rtc.clock_second_part();
+
+        false
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(Tama5, 128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_register(tama5: &mut Tama5, register: u8, value: u8) {
+        tama5.map_ram_write(0xA001, register);
+        tama5.map_ram_write(0xA000, value);
+    }
+
+    fn read_result(tama5: &mut Tama5) -> u8 {
+        let mut read = |register| {
+            tama5.map_ram_write(0xA001, register);
+            match tama5.map_ram_read(0xA000) {
+                MappingResult::Value(value) => value & 0xF,
+                _ => unreachable!(),
+            }
+        };
+
+        read(register::RESULT_HIGH) << 4 | read(register::RESULT_LOW)
+    }
+
+    /// Executes a command on `addr`
+    fn command(tama5: &mut Tama5, command: u8, addr: u8) {
+        write_register(tama5, register::ADDR_HIGH, command << 1 | addr >> 4);
+        write_register(tama5, register::ADDR_LOW, addr & 0xF);
+    }
+
+    #[test]
+    fn ram_commands() {
+        let mut tama5 = Tama5::default();
+        tama5.init(32, 0);
+
+        write_register(&mut tama5, register::DATA_LOW, 0xD);
+        write_register(&mut tama5, register::DATA_HIGH, 0xB);
+        command(&mut tama5, 0, 0x13);
+        assert!(tama5.take_battery_written());
+
+        command(&mut tama5, 1, 0x13);
+        assert_eq!(read_result(&mut tama5), 0xBD);
+    }
+
+    #[test]
+    fn rtc_commands_and_alarm() {
+        let mut tama5 = Tama5::default();
+        tama5.init(32, 0);
+
+        // 23:59:59, alarm at 00:00
+        for &(addr, value) in &[
+            (0x05, 2),
+            (0x04, 3),
+            (0x03, 5),
+            (0x02, 9),
+            (0x01, 5),
+            (0x00, 9),
+        ] {
+            write_register(&mut tama5, register::DATA_LOW, value);
+            command(&mut tama5, 2, addr);
+        }
+        write_register(&mut tama5, register::DATA_LOW, 1);
+        command(&mut tama5, 2, 0x1D);
+
+        for _ in 0..ONE_SECOND_MAPPER_CLOCKS {
+            tama5.clock(&mut []);
+        }
+
+        command(&mut tama5, 3, 0x05);
+        assert_eq!(read_result(&mut tama5), 0);
+        command(&mut tama5, 3, 0x07);
+        assert_eq!(read_result(&mut tama5), 2);
+        command(&mut tama5, 3, 0x1D);
+        assert_eq!(read_result(&mut tama5), 3);
+    }
+
+    #[test]
+    fn battery_round_trip() {
+        let mut tama5 = Tama5::default();
+        tama5.ram[3] = 0x42;
+        tama5.rtc.month = 7;
+
+        let data = tama5.save_battery();
+        assert_eq!(data.len(), RAM_SIZE + RTC_FOOTER_SIZE);
+
+        let mut loaded = Tama5::default();
+        assert!(loaded.load_battery(&data));
+        assert_eq!(loaded.ram[3], 0x42);
+        assert_eq!(loaded.rtc.month, 7);
+    }
+}
@@ -0,0 +1,88 @@
+use super::{Mapper, MappingResult};
+use crate::save_state::{load_object, save_object, SaveError};
+use serde::{Deserialize, Serialize};
+
+/// Unlicensed mapper used by Wisdom Tree games, it switches the whole
+/// `0x0000-0x7FFF` range in 32KB banks, the bank number is the lower byte of
+/// the address written to in `0x0000-0x3FFF` (the data is ignored)
+#[derive(Default, Serialize, Deserialize)]
+pub struct WisdomTree {
+    rom_banks: u16,
+    bank: u8,
+}
+
+impl WisdomTree {
+    #[inline]
+    fn bank_offset(&self) -> usize {
+        // in 32KB banks
+        let banks = (self.rom_banks / 2).max(1);
+
+        (self.bank as u16 % banks) as usize * 0x8000
+    }
+}
+
+impl Mapper for WisdomTree {
+    fn init(&mut self, rom_banks: u16, _ram_size: usize) {
+        self.rom_banks = rom_banks;
+    }
+
+    fn map_read_rom0(&self, addr: u16) -> usize {
+        self.bank_offset() + addr as usize
+    }
+
+    fn map_read_romx(&self, addr: u16) -> MappingResult {
+        MappingResult::Addr(self.bank_offset() + addr as usize)
+    }
+
+    fn map_ram_read(&mut self, _addr: u16) -> MappingResult {
+        MappingResult::NotMapped
+    }
+
+    fn map_ram_write(&mut self, _addr: u16, _data: u8) -> MappingResult {
+        MappingResult::NotMapped
+    }
+
+    fn write_bank_controller_register(&mut self, addr: u16, _data: u8) {
+        if let 0x0000..=0x3FFF = addr {
+            self.bank = addr as u8;
+        }
+    }
+
+    fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        save_object(self)
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        load_object(self, data)
+    }
+}
+
+impl_savable!(WisdomTree, 8);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_32kb_banks() {
+        let mut wisdom_tree = WisdomTree::default();
+        // 4 banks of 32KB
+        wisdom_tree.init(8, 0);
+
+        // the bank is the lower byte of the address, the data is ignored
+        wisdom_tree.write_bank_controller_register(0x3F02, 0xFF);
+        assert_eq!(wisdom_tree.map_read_rom0(0x0100), 0x10100);
+        assert!(matches!(
+            wisdom_tree.map_read_romx(0x4000),
+            MappingResult::Addr(0x14000)
+        ));
+
+        // wraps around the number of banks
+        wisdom_tree.write_bank_controller_register(0x0005, 0);
+        assert_eq!(wisdom_tree.map_read_rom0(0x0000), 0x8000);
+
+        // writes outside `0x0000-0x3FFF` are ignored
+        wisdom_tree.write_bank_controller_register(0x4003, 0);
+        assert_eq!(wisdom_tree.map_read_rom0(0x0000), 0x8000);
+    }
+}
@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::{ErrorKind as ioErrorKind, Result as ioResult};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// Where the battery backed SRAM of a cartridge is stored, the data is the
+/// SRAM followed by the extra data of the mapper (RTC for example), which is
+/// the same format of `.sav` files.
+///
+/// Errors can be reported using [`std::io::Error::new`] for storages that
+/// are not based on files.
+pub trait SramStorage {
+    /// Returns `None` if nothing was saved before
+    fn load(&mut self) -> ioResult<Option<Vec<u8>>>;
+
+    fn save(&mut self, data: &[u8]) -> ioResult<()>;
+}
+
+/// Stores the SRAM in a file, this is the default storage used by
+/// [`GameBoy::new`](crate::GameBoy::new), which stores the save file next to
+/// the ROM file.
+pub struct FileSramStorage {
+    path: PathBuf,
+}
+
+impl FileSramStorage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Uses the file `<rom>.gb.sav` next to the ROM file
+    pub fn next_to_rom<P: AsRef<Path>>(rom_path: P) -> Self {
+        let rom_path = rom_path.as_ref();
+        let extension = rom_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        Self::new(rom_path.with_extension(format!("{}.sav", extension)))
+    }
+
+    /// Uses the file `<rom>.gb.sav` in `directory`, where `<rom>.gb` is the
+    /// file name of the ROM
+    pub fn in_directory<P: AsRef<Path>, Q: AsRef<Path>>(directory: P, rom_path: Q) -> Self {
+        let file_name = rom_path
+            .as_ref()
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Self::new(directory.as_ref().join(format!("{}.sav", file_name)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl SramStorage for FileSramStorage {
+    fn load(&mut self) -> ioResult<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == ioErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&mut self, data: &[u8]) -> ioResult<()> {
+        // write to a temporary file first, so that a crash in the middle
+        // of writing does not corrupt the old save file
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+/// Stores the SRAM in memory, clones of this storage share the same data,
+/// so a clone can be kept to read the saved data after giving the storage to
+/// [`GameBoy::set_sram_storage`](crate::GameBoy::set_sram_storage).
+#[derive(Clone, Default)]
+pub struct MemorySramStorage {
+    data: Rc<RefCell<Option<Vec<u8>>>>,
+}
+
+impl MemorySramStorage {
+    /// Creates a storage that starts with `data` saved
+    pub fn with_data(data: Vec<u8>) -> Self {
+        Self {
+            data: Rc::new(RefCell::new(Some(data))),
+        }
+    }
+
+    /// The last saved data
+    pub fn data(&self) -> Option<Vec<u8>> {
+        self.data.borrow().clone()
+    }
+}
+
+impl SramStorage for MemorySramStorage {
+    fn load(&mut self) -> ioResult<Option<Vec<u8>>> {
+        Ok(self.data())
+    }
+
+    fn save(&mut self, data: &[u8]) -> ioResult<()> {
+        *self.data.borrow_mut() = Some(data.to_vec());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs;
+use std::io::{Error as ioError, ErrorKind as ioErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CheatError {
+    #[error("The code {0:?} is not a valid Game Genie or GameShark code")]
+    InvalidCode(String),
+    #[error("The Game Genie code {0:?} patches an address outside the ROM (0x0000-0x7FFF)")]
+    AddressOutOfRom(String),
+    #[error("Line {line} of the cheats file is invalid")]
+    InvalidCheatsFile { line: usize },
+    #[error("The game was not loaded from a file, so there is no cheats file")]
+    NoCheatsFile,
+    #[error("File error: {0}")]
+    FileError(#[from] ioError),
+}
+
+/// A parsed cheat code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    /// Replaces the value read from the ROM at `addr` with `value`, if
+    /// `compare` is present, the value is only replaced if the original value
+    /// matches it, which is used to only patch one ROM bank
+    GameGenie {
+        addr: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+    /// Writes `value` to `addr` in RAM every frame, if `bank` is present
+    /// the value is written to that WRAM or SRAM bank instead of the bank
+    /// currently mapped
+    GameShark {
+        addr: u16,
+        value: u8,
+        bank: Option<u8>,
+    },
+}
+
+impl CheatCode {
+    /// Game Genie codes are in the form `VVA-AAA-CCC` or `VVA-AAA`, and
+    /// GameShark codes are in the form `TTVVLLHH`
+    pub fn parse(code: &str) -> Result<Self, CheatError> {
+        let digits = code
+            .chars()
+            .filter(|&c| c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| CheatError::InvalidCode(code.to_string()))?;
+
+        let byte = |i: usize| (digits[i] << 4) | digits[i + 1];
+
+        match digits.len() {
+            6 | 9 if code.contains('-') => {
+                let value = byte(0);
+                // the 6th digit is the top nibble of the address, inverted
+                let addr = ((digits[5] as u16) << 12
+                    | (digits[2] as u16) << 8
+                    | (digits[3] as u16) << 4
+                    | digits[4] as u16)
+                    ^ 0xF000;
+
+                if addr > 0x7FFF {
+                    return Err(CheatError::AddressOutOfRom(code.to_string()));
+                }
+
+                // the 8th digit is a checksum and is ignored
+                let compare = if digits.len() == 9 {
+                    let compare = (digits[6] << 4) | digits[8];
+                    Some(compare.rotate_right(2) ^ 0xBA)
+                } else {
+                    None
+                };
+
+                Ok(CheatCode::GameGenie {
+                    addr,
+                    value,
+                    compare,
+                })
+            }
+            8 if !code.contains('-') => {
+                let code_type = byte(0);
+                let value = byte(2);
+                let addr = u16::from_le_bytes([byte(4), byte(6)]);
+
+                // 0x8X and 0x9X write to bank X, the others write to the
+                // current bank
+                let bank = if code_type & 0xE0 == 0x80 {
+                    Some(code_type & 0xF)
+                } else {
+                    None
+                };
+
+                Ok(CheatCode::GameShark { addr, value, bank })
+            }
+            _ => Err(CheatError::InvalidCode(code.to_string())),
+        }
+    }
+}
+
+impl FromStr for CheatCode {
+    type Err = CheatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cheat {
+    code: String,
+    parsed: CheatCode,
+    name: String,
+    enabled: bool,
+}
+
+impl Cheat {
+    /// The code as entered by the user
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn parsed(&self) -> CheatCode {
+        self.parsed
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// A Game Genie patch to apply when reading from the ROM
+#[derive(Debug, Clone, Copy)]
+pub struct RomPatch {
+    addr: u16,
+    value: u8,
+    compare: Option<u8>,
+}
+
+impl RomPatch {
+    /// Returns the value to be read from `addr` in place of `original`
+    #[inline]
+    pub fn apply(&self, addr: u16, original: u8) -> Option<u8> {
+        if self.addr == addr && self.compare.is_none_or(|compare| compare == original) {
+            Some(self.value)
+        } else {
+            None
+        }
+    }
+}
+
+/// The list of cheats of a game, the cheats can be saved to a file next to
+/// the ROM, and they are loaded automatically when the game is loaded.
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    /// Adds a new enabled cheat, and returns its index
+    pub fn add(&mut self, code: &str, name: &str) -> Result<usize, CheatError> {
+        let parsed = CheatCode::parse(code)?;
+
+        self.cheats.push(Cheat {
+            code: code.to_uppercase(),
+            parsed,
+            name: name.to_string(),
+            enabled: true,
+        });
+
+        Ok(self.cheats.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        if index < self.cheats.len() {
+            Some(self.cheats.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `false` if there is no cheat with this index
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub(crate) fn rom_patches(&self) -> Vec<RomPatch> {
+        self.enabled_codes()
+            .filter_map(|code| match code {
+                CheatCode::GameGenie {
+                    addr,
+                    value,
+                    compare,
+                } => Some(RomPatch {
+                    addr,
+                    value,
+                    compare,
+                }),
+                CheatCode::GameShark { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Returns `(addr, value, bank)` of the enabled GameShark codes
+    pub(crate) fn ram_patches(&self) -> impl Iterator<Item = (u16, u8, Option<u8>)> + '_ {
+        self.enabled_codes().filter_map(|code| match code {
+            CheatCode::GameShark { addr, value, bank } => Some((addr, value, bank)),
+            CheatCode::GameGenie { .. } => None,
+        })
+    }
+
+    fn enabled_codes(&self) -> impl Iterator<Item = CheatCode> + '_ {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .map(|cheat| cheat.parsed)
+    }
+
+    /// Loads the cheats from a file created by [`save_to_file`](Self::save_to_file),
+    /// returns an empty list if the file does not exist
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, CheatError> {
+        match fs::read_to_string(path) {
+            Ok(content) => content.parse(),
+            Err(err) if err.kind() == ioErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), CheatError> {
+        fs::write(path, self.to_string())?;
+
+        Ok(())
+    }
+}
+
+/// Every cheat is stored in one line in the form `<+|-> <code> <name>`,
+/// where `+` means the cheat is enabled
+impl Display for Cheats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for cheat in &self.cheats {
+            let enabled = if cheat.enabled { '+' } else { '-' };
+            writeln!(f, "{} {} {}", enabled, cheat.code, cheat.name)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Cheats {
+    type Err = CheatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cheats = Self::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = || CheatError::InvalidCheatsFile { line: i + 1 };
+
+            let mut parts = line.splitn(3, ' ');
+            let enabled = match parts.next() {
+                Some("+") => true,
+                Some("-") => false,
+                _ => return Err(error()),
+            };
+            let code = parts.next().ok_or_else(error)?;
+            let name = parts.next().unwrap_or("").trim();
+
+            let index = cheats.add(code, name).map_err(|_| error())?;
+            cheats.set_enabled(index, enabled);
+        }
+
+        Ok(cheats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_game_genie() {
+        assert_eq!(
+            CheatCode::parse("00A-17B-C49").unwrap(),
+            CheatCode::GameGenie {
+                addr: 0x4A17,
+                value: 0x00,
+                compare: Some(0xC8),
+            }
+        );
+        assert_eq!(
+            CheatCode::parse("3EA-17B").unwrap(),
+            CheatCode::GameGenie {
+                addr: 0x4A17,
+                value: 0x3E,
+                compare: None,
+            }
+        );
+        assert!(matches!(
+            CheatCode::parse("00A-170-C49"),
+            Err(CheatError::AddressOutOfRom(_))
+        ));
+    }
+
+    #[test]
+    fn parse_gameshark() {
+        assert_eq!(
+            CheatCode::parse("010538C1").unwrap(),
+            CheatCode::GameShark {
+                addr: 0xC138,
+                value: 0x05,
+                bank: None,
+            }
+        );
+        assert_eq!(
+            CheatCode::parse("9263F4D2").unwrap(),
+            CheatCode::GameShark {
+                addr: 0xD2F4,
+                value: 0x63,
+                bank: Some(2),
+            }
+        );
+        assert!(CheatCode::parse("0105-38C1").is_err());
+        assert!(CheatCode::parse("01G538C1").is_err());
+    }
+
+    #[test]
+    fn cheats_file_round_trip() {
+        let mut cheats = Cheats::default();
+        cheats.add("00a-17b-c49", "infinite lives").unwrap();
+        let index = cheats.add("010538C1", "").unwrap();
+        cheats.set_enabled(index, false);
+
+        let parsed = cheats.to_string().parse::<Cheats>().unwrap();
+        assert_eq!(parsed.cheats().len(), 2);
+        assert_eq!(parsed.cheats()[0].code(), "00A-17B-C49");
+        assert_eq!(parsed.cheats()[0].name(), "infinite lives");
+        assert!(parsed.cheats()[0].is_enabled());
+        assert!(!parsed.cheats()[1].is_enabled());
+
+        assert_eq!(parsed.rom_patches().len(), 1);
+        assert_eq!(parsed.ram_patches().count(), 0);
+    }
+}
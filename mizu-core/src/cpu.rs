@@ -27,6 +27,12 @@ pub trait CpuBusProvider {
     fn trigger_read_write_oam_bug(&mut self, addr: u16);
     /// reads data without triggering oam_bug, this is used in pop
     fn read_no_oam_bug(&mut self, addr: u16) -> u8;
+
+    /// Should `trace_instruction` be called before every instruction
+    fn is_tracing(&self) -> bool;
+    /// Called with the registers before executing every instruction, only if
+    /// `is_tracing` returns `true`
+    fn trace_instruction(&mut self, registers: CpuRegisters);
 }
 
 const INTERRUPTS_VECTOR: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
@@ -54,6 +60,9 @@ pub enum CpuState {
     Stopped,
     RunningInterrupt(InterruptType),
     Breakpoint(CpuRegisters),
+    /// An illegal opcode was executed, the CPU does not execute anything
+    /// after that until reset
+    Locked,
 }
 
 bitflags! {
@@ -94,6 +103,9 @@ pub struct Cpu {
     halt_mode: HaltMode,
 
     config: GameboyConfig,
+
+    /// The address of the illegal opcode that locked the CPU
+    locked_at: Option<u16>,
 }
 
 impl Cpu {
@@ -115,6 +127,8 @@ impl Cpu {
             halt_mode: HaltMode::NotHalting,
 
             config,
+
+            locked_at: None,
         }
     }
 
@@ -150,7 +164,17 @@ impl Cpu {
         cpu
     }
 
+    /// Returns the address of the illegal opcode if the CPU is locked
+    pub fn locked_at(&self) -> Option<u16> {
+        self.locked_at
+    }
+
     pub fn next_instruction<P: CpuBusProvider>(&mut self, bus: &mut P) -> CpuState {
+        if self.locked_at.is_some() {
+            self.advance_bus(bus);
+            return CpuState::Locked;
+        }
+
         if bus.stopped() {
             self.advance_bus(bus);
             return CpuState::Stopped;
@@ -214,6 +238,10 @@ impl Cpu {
             self.enable_interrupt_next = false;
         }
 
+        if bus.is_tracing() {
+            bus.trace_instruction(self.registers());
+        }
+
         let pc = self.reg_pc;
         let mut instruction = Instruction::from_byte(self.fetch_next_pc(bus), pc);
 
@@ -287,7 +315,7 @@ impl Cpu {
         self.reg_f.set(flag, value);
     }
 
-    fn registers(&self) -> CpuRegisters {
+    pub fn registers(&self) -> CpuRegisters {
         CpuRegisters {
             a: self.reg_a,
             b: self.reg_b,
@@ -924,16 +952,17 @@ impl Cpu {
                 bus.enter_stop_mode();
                 0
             }
-            Opcode::Illegal => todo!(),
+            Opcode::Illegal => {
+                // the CPU hangs, but the rest of the hardware keeps running
+                self.locked_at = Some(instruction.pc);
+                self.reg_pc = instruction.pc;
+                cpu_state = CpuState::Locked;
+
+                0
+            }
             Opcode::Prefix => unreachable!(),
         };
 
-        // DEBUG
-        // println!(
-        //     "{:04X}: {}, src={:04X}, result={:04X}",
-        //     instruction.pc, instruction, src, result
-        // );
-
         self.write_operand(instruction.dest, result, bus);
 
         cpu_state
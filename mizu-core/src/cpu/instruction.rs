@@ -2,7 +2,7 @@ use super::instructions_table;
 use std::fmt::Display;
 
 #[derive(Debug)]
-pub(super) struct Instruction {
+pub(crate) struct Instruction {
     pub pc: u16,
     pub opcode: Opcode,
     pub src: OperandType,
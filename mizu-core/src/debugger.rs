@@ -0,0 +1,148 @@
+/// The reason the emulation stopped before finishing what it was asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// The CPU is about to execute the instruction at this address, which has
+    /// a breakpoint
+    Breakpoint(u16),
+    /// The value `value` was read from the watched address `addr`
+    ReadWatchpoint { addr: u16, value: u8 },
+    /// The value `value` was written to the watched address `addr`
+    WriteWatchpoint { addr: u16, value: u8 },
+    /// The CPU executed the illegal opcode at this address and hung, the
+    /// rest of the hardware keeps running, but no more instructions are
+    /// executed. This is only reported once, when the CPU locks.
+    CpuLocked(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn watches_read(self) -> bool {
+        self != WatchKind::Write
+    }
+
+    fn watches_write(self) -> bool {
+        self != WatchKind::Read
+    }
+}
+
+/// Holds the breakpoints and watchpoints used while running the emulation,
+/// can be accessed with [`GameBoy::debugger`](crate::GameBoy::debugger).
+///
+/// Watchpoints are checked on every access to the bus, including accesses
+/// done by DMA.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<(u16, WatchKind)>,
+
+    /// The first watchpoint hit since the last time it was taken
+    watchpoint_hit: Option<BreakReason>,
+    /// Used to skip the breakpoint of the instruction we stopped at when
+    /// resuming execution
+    skip_breakpoint: bool,
+    /// Disables watchpoints, used when the debugger itself reads memory
+    muted: bool,
+}
+
+impl Debugger {
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        if !self.breakpoints.contains(&addr) {
+            self.breakpoints.push(addr);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.retain(|&breakpoint| breakpoint != addr);
+    }
+
+    pub fn breakpoints(&self) -> &[u16] {
+        &self.breakpoints
+    }
+
+    /// Adds a watchpoint to `addr`, replacing the old one if it exists
+    pub fn add_watchpoint(&mut self, addr: u16, kind: WatchKind) {
+        self.remove_watchpoint(addr);
+        self.watchpoints.push((addr, kind));
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: u16) {
+        self.watchpoints
+            .retain(|&(watchpoint, _)| watchpoint != addr);
+    }
+
+    pub fn watchpoints(&self) -> &[(u16, WatchKind)] {
+        &self.watchpoints
+    }
+
+    /// Removes all breakpoints and watchpoints
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.watchpoint_hit = None;
+    }
+}
+
+impl Debugger {
+    /// Returns a `BreakReason` if there is a breakpoint at `pc`, must be
+    /// called before executing every instruction, as it also clears the skip
+    /// set by [`skip_next_breakpoint`](Self::skip_next_breakpoint)
+    pub(crate) fn check_breakpoint(&mut self, pc: u16) -> Option<BreakReason> {
+        if std::mem::replace(&mut self.skip_breakpoint, false) || self.breakpoints.is_empty() {
+            return None;
+        }
+
+        if self.breakpoints.contains(&pc) {
+            // when execution is resumed, the instruction should be executed
+            self.skip_breakpoint = true;
+            Some(BreakReason::Breakpoint(pc))
+        } else {
+            None
+        }
+    }
+
+    /// Don't stop on a breakpoint in the next instruction
+    pub(crate) fn skip_next_breakpoint(&mut self) {
+        self.skip_breakpoint = true;
+    }
+
+    #[inline]
+    pub(crate) fn has_watchpoints(&self) -> bool {
+        !self.watchpoints.is_empty() && !self.muted
+    }
+
+    pub(crate) fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub(crate) fn on_read(&mut self, addr: u16, value: u8) {
+        if self.watchpoint_hit.is_none()
+            && self
+                .watchpoints
+                .iter()
+                .any(|&(watchpoint, kind)| watchpoint == addr && kind.watches_read())
+        {
+            self.watchpoint_hit = Some(BreakReason::ReadWatchpoint { addr, value });
+        }
+    }
+
+    pub(crate) fn on_write(&mut self, addr: u16, value: u8) {
+        if self.watchpoint_hit.is_none()
+            && self
+                .watchpoints
+                .iter()
+                .any(|&(watchpoint, kind)| watchpoint == addr && kind.watches_write())
+        {
+            self.watchpoint_hit = Some(BreakReason::WriteWatchpoint { addr, value });
+        }
+    }
+
+    pub(crate) fn take_watchpoint_hit(&mut self) -> Option<BreakReason> {
+        self.watchpoint_hit.take()
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::BTreeMap;
+
+use crate::cpu::instruction::{Condition, Instruction, Opcode, OperandType};
+
+/// Labels of addresses, used to annotate the disassembly.
+///
+/// Each label is for an address in a specific bank, the bank is only
+/// important for banked memory regions (ROMX, VRAM, SRAM and WRAMX).
+#[derive(Default, Debug, Clone)]
+pub struct Labels {
+    labels: BTreeMap<(u16, u16), String>,
+}
+
+impl Labels {
+    /// Parses an RGBDS `.sym` file, each line is `BB:AAAA Label` where `BB`
+    /// is the bank and `AAAA` is the address in hex. Comments (starting with
+    /// `;`) and invalid lines are ignored.
+    pub fn from_sym(content: &str) -> Self {
+        let mut labels = Self::default();
+
+        for line in content.lines() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+
+            let mut parts = line.split_whitespace();
+            let (location, name) = match (parts.next(), parts.next()) {
+                (Some(location), Some(name)) => (location, name),
+                _ => continue,
+            };
+
+            let mut location = location.split(':');
+            let (bank, addr) = match (location.next(), location.next()) {
+                (Some(bank), Some(addr)) => (bank, addr),
+                _ => continue,
+            };
+
+            if let (Ok(bank), Ok(addr)) =
+                (u16::from_str_radix(bank, 16), u16::from_str_radix(addr, 16))
+            {
+                labels.insert(bank, addr, name);
+            }
+        }
+
+        labels
+    }
+
+    pub fn insert(&mut self, bank: u16, addr: u16, name: &str) {
+        self.labels.insert((bank, addr), name.to_string());
+    }
+
+    /// Returns the label of `addr` in `bank`, if `bank` is `None`, returns
+    /// the label of `addr` in the lowest bank that has one.
+    pub fn get(&self, bank: Option<u16>, addr: u16) -> Option<&str> {
+        match bank {
+            Some(bank) => self.labels.get(&(bank, addr)),
+            None => self
+                .labels
+                .iter()
+                .find(|((_, label_addr), _)| *label_addr == addr)
+                .map(|(_, name)| name),
+        }
+        .map(|name| name.as_str())
+    }
+
+    fn get_for_bank(&self, rom_bank: Option<u16>, addr: u16) -> Option<&str> {
+        match addr {
+            // ROM0 is always bank 0
+            0x0000..=0x3FFF => self.get(Some(0), addr),
+            0x4000..=0x7FFF => self.get(rom_bank, addr),
+            _ => self.get(None, addr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    /// The address of the first byte of the instruction
+    pub addr: u16,
+    /// The bytes of the instruction, the length of the instruction is the
+    /// length of this
+    pub bytes: Vec<u8>,
+    /// The instruction with its operands, e.g. `LD A, ($C000)`
+    pub mnemonic: String,
+    /// The address this instruction may jump to (`JP`, `JR`, `CALL` and `RST`)
+    pub target: Option<u16>,
+    /// The label of `addr` if any
+    pub label: Option<String>,
+}
+
+impl DisassembledInstruction {
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+fn opcode_str(opcode: Opcode) -> String {
+    let condition = |name: &str, cond: Condition| {
+        if cond == Condition::Unconditional {
+            name.to_string()
+        } else {
+            format!("{} {:?},", name, cond)
+        }
+    };
+
+    match opcode {
+        Opcode::Nop => "NOP".into(),
+        Opcode::Stop => "STOP".into(),
+        Opcode::Ld | Opcode::LdBB | Opcode::LdSPHL | Opcode::LdHLSPSigned8 => "LD".into(),
+        Opcode::Push => "PUSH".into(),
+        Opcode::Pop => "POP".into(),
+        Opcode::Inc | Opcode::Inc16 => "INC".into(),
+        Opcode::Dec | Opcode::Dec16 => "DEC".into(),
+        Opcode::Add | Opcode::Add16 | Opcode::AddSPSigned8 => "ADD".into(),
+        Opcode::Adc => "ADC".into(),
+        Opcode::Cp => "CP".into(),
+        Opcode::Sub => "SUB".into(),
+        Opcode::Sbc => "SBC".into(),
+        Opcode::And => "AND".into(),
+        Opcode::Xor => "XOR".into(),
+        Opcode::Or => "OR".into(),
+        Opcode::Jp(cond) => condition("JP", cond),
+        Opcode::JpHL => "JP".into(),
+        Opcode::Jr(cond) => condition("JR", cond),
+        Opcode::Call(cond) => condition("CALL", cond),
+        Opcode::Ret(Condition::Unconditional) => "RET".into(),
+        Opcode::Ret(cond) => format!("RET {:?}", cond),
+        Opcode::Reti => "RETI".into(),
+        Opcode::Rst(_) => "RST".into(),
+        Opcode::Di => "DI".into(),
+        Opcode::Ei => "EI".into(),
+        Opcode::Ccf => "CCF".into(),
+        Opcode::Scf => "SCF".into(),
+        Opcode::Daa => "DAA".into(),
+        Opcode::Cpl => "CPL".into(),
+        Opcode::Rlca => "RLCA".into(),
+        Opcode::Rla => "RLA".into(),
+        Opcode::Rrca => "RRCA".into(),
+        Opcode::Rra => "RRA".into(),
+        Opcode::Prefix => "PREFIX".into(),
+        Opcode::Rlc => "RLC".into(),
+        Opcode::Rrc => "RRC".into(),
+        Opcode::Rl => "RL".into(),
+        Opcode::Rr => "RR".into(),
+        Opcode::Sla => "SLA".into(),
+        Opcode::Sra => "SRA".into(),
+        Opcode::Swap => "SWAP".into(),
+        Opcode::Srl => "SRL".into(),
+        Opcode::Bit(n) => format!("BIT {},", n),
+        Opcode::Res(n) => format!("RES {},", n),
+        Opcode::Set(n) => format!("SET {},", n),
+        Opcode::Illegal => "DB".into(),
+        Opcode::Halt => "HALT".into(),
+    }
+}
+
+/// The number of bytes the operand takes after the opcode
+fn operand_len(operand: OperandType) -> u16 {
+    match operand {
+        OperandType::Imm8 | OperandType::Imm8Signed | OperandType::HighAddr8 => 1,
+        OperandType::Imm16 | OperandType::Addr16 | OperandType::Addr16Val16 => 2,
+        _ => 0,
+    }
+}
+
+/// Disassembles instructions one by one, reading bytes through `read`
+struct InstructionDecoder<'a, F: FnMut(u16) -> u8> {
+    read: F,
+    labels: Option<&'a Labels>,
+    rom_bank: Option<u16>,
+}
+
+impl<'a, F: FnMut(u16) -> u8> InstructionDecoder<'a, F> {
+    fn label(&self, addr: u16) -> Option<String> {
+        self.labels
+            .and_then(|labels| labels.get_for_bank(self.rom_bank, addr))
+            .map(|name| name.to_string())
+    }
+
+    fn addr_str(&self, addr: u16) -> String {
+        self.label(addr).unwrap_or_else(|| format!("${:04X}", addr))
+    }
+
+    fn operand_str(&self, operand: OperandType, imm: u16) -> String {
+        match operand {
+            OperandType::RegA => "A".into(),
+            OperandType::RegB => "B".into(),
+            OperandType::RegC => "C".into(),
+            OperandType::RegD => "D".into(),
+            OperandType::RegE => "E".into(),
+            OperandType::RegH => "H".into(),
+            OperandType::RegL => "L".into(),
+            OperandType::AddrHL => "(HL)".into(),
+            OperandType::AddrHLDec => "(HL-)".into(),
+            OperandType::AddrHLInc => "(HL+)".into(),
+            OperandType::AddrBC => "(BC)".into(),
+            OperandType::AddrDE => "(DE)".into(),
+            OperandType::RegAF => "AF".into(),
+            OperandType::RegBC => "BC".into(),
+            OperandType::RegDE => "DE".into(),
+            OperandType::RegHL => "HL".into(),
+            OperandType::RegSP => "SP".into(),
+            OperandType::Imm8 => format!("${:02X}", imm),
+            OperandType::Imm8Signed => {
+                let offset = imm as u8 as i8;
+                format!(
+                    "{}{}",
+                    if offset < 0 { "-" } else { "+" },
+                    (offset as i16).abs()
+                )
+            }
+            OperandType::Imm16 => format!("${:04X}", imm),
+            OperandType::HighAddr8 => format!("({})", self.addr_str(0xFF00 | imm)),
+            OperandType::HighAddrC => "($FF00+C)".into(),
+            OperandType::Addr16 | OperandType::Addr16Val16 => format!("({})", self.addr_str(imm)),
+            OperandType::Implied => "".into(),
+        }
+    }
+
+    fn decode(&mut self, addr: u16) -> DisassembledInstruction {
+        let opcode_byte = (self.read)(addr);
+        let mut bytes = vec![opcode_byte];
+        let mut instruction = Instruction::from_byte(opcode_byte, addr);
+
+        if instruction.opcode == Opcode::Prefix {
+            let byte = (self.read)(addr.wrapping_add(1));
+            bytes.push(byte);
+            instruction = Instruction::from_prefix(byte, addr);
+        }
+
+        let imm_len = operand_len(instruction.src) + operand_len(instruction.dest);
+        let mut imm = 0;
+        for i in 0..imm_len {
+            let byte = (self.read)(addr.wrapping_add(bytes.len() as u16));
+            bytes.push(byte);
+            imm |= (byte as u16) << (i * 8);
+        }
+
+        // `STOP` is followed by a byte that is ignored by the CPU, but is
+        // always emitted by assemblers
+        if instruction.opcode == Opcode::Stop {
+            bytes.push((self.read)(addr.wrapping_add(1)));
+        }
+
+        let next_pc = addr.wrapping_add(bytes.len() as u16);
+
+        let target = match instruction.opcode {
+            Opcode::Jp(_) | Opcode::Call(_) => Some(imm),
+            Opcode::Jr(_) => Some(next_pc.wrapping_add(imm as u8 as i8 as u16)),
+            Opcode::Rst(loc) => Some(loc as u16),
+            _ => None,
+        };
+
+        let operands = if let Some(target) = target {
+            self.addr_str(target)
+        } else {
+            match instruction.opcode {
+                Opcode::Illegal => format!("${:02X}", opcode_byte),
+                Opcode::LdBB => "B, B".into(),
+                Opcode::LdSPHL => "SP, HL".into(),
+                Opcode::LdHLSPSigned8 => {
+                    format!("HL, SP{}", self.operand_str(instruction.src, imm))
+                }
+                Opcode::AddSPSigned8 => format!("SP, {}", self.operand_str(instruction.src, imm)),
+                // single operand instructions have the same `src` and `dest`
+                Opcode::Inc
+                | Opcode::Inc16
+                | Opcode::Dec
+                | Opcode::Dec16
+                | Opcode::Rlc
+                | Opcode::Rrc
+                | Opcode::Rl
+                | Opcode::Rr
+                | Opcode::Sla
+                | Opcode::Sra
+                | Opcode::Swap
+                | Opcode::Srl
+                | Opcode::Res(_)
+                | Opcode::Set(_) => self.operand_str(instruction.dest, imm),
+                _ => {
+                    let dest = self.operand_str(instruction.dest, imm);
+                    let src = self.operand_str(instruction.src, imm);
+
+                    match (dest.is_empty(), src.is_empty()) {
+                        (false, false) => format!("{}, {}", dest, src),
+                        (false, true) => dest,
+                        (true, _) => src,
+                    }
+                }
+            }
+        };
+
+        let opcode = opcode_str(instruction.opcode);
+        let mnemonic = if operands.is_empty() {
+            opcode
+        } else {
+            format!("{} {}", opcode, operands)
+        };
+
+        DisassembledInstruction {
+            addr,
+            bytes,
+            mnemonic,
+            target,
+            label: self.label(addr),
+        }
+    }
+}
+
+/// Disassembles `data`, which is located at `start_addr` in the memory map.
+///
+/// `rom_bank` is used to select the labels of addresses in `0x4000..=0x7FFF`,
+/// if it is `None`, labels of any bank are used.
+///
+/// If the last instruction is cut at the end of `data`, its remaining bytes
+/// are emitted as `DB` instructions.
+pub fn disassemble(
+    data: &[u8],
+    start_addr: u16,
+    rom_bank: Option<u16>,
+    labels: Option<&Labels>,
+) -> Vec<DisassembledInstruction> {
+    let end_addr = start_addr as usize + data.len();
+
+    let mut decoder = InstructionDecoder {
+        read: |addr: u16| {
+            data.get((addr as usize).wrapping_sub(start_addr as usize))
+                .copied()
+                .unwrap_or(0)
+        },
+        labels,
+        rom_bank,
+    };
+
+    let mut result = Vec::new();
+    let mut addr = start_addr as usize;
+
+    while addr < end_addr {
+        let instruction = decoder.decode(addr as u16);
+
+        if addr + instruction.len() > end_addr {
+            // the instruction is not complete, the remaining bytes are data
+            let mut label = instruction.label;
+
+            for (i, &byte) in data[addr - start_addr as usize..].iter().enumerate() {
+                result.push(DisassembledInstruction {
+                    addr: (addr + i) as u16,
+                    bytes: vec![byte],
+                    mnemonic: format!("DB ${:02X}", byte),
+                    target: None,
+                    label: label.take(),
+                });
+            }
+            break;
+        } else {
+            addr += instruction.len();
+            result.push(instruction);
+        }
+    }
+
+    result
+}
+
+/// Disassembles the ROM bank `bank` of the cartridge `rom`, bank `0` is
+/// located at `0x0000` and the others are located at `0x4000`.
+pub fn disassemble_rom_bank(
+    rom: &[u8],
+    bank: u16,
+    labels: Option<&Labels>,
+) -> Vec<DisassembledInstruction> {
+    let start = bank as usize * 0x4000;
+    let end = (start + 0x4000).min(rom.len());
+    let data = rom.get(start..end).unwrap_or_default();
+
+    let start_addr = if bank == 0 { 0x0000 } else { 0x4000 };
+
+    disassemble(data, start_addr, Some(bank), labels)
+}
+
+/// Disassembles `count` instructions starting from `addr` reading through
+/// `read`, used to disassemble the memory of a running emulator.
+///
+/// `rom_bank` is the bank mapped at `0x4000..=0x7FFF`, used to select labels.
+pub(crate) fn disassemble_with<F: FnMut(u16) -> u8>(
+    read: F,
+    mut addr: u16,
+    count: usize,
+    rom_bank: Option<u16>,
+    labels: Option<&Labels>,
+) -> Vec<DisassembledInstruction> {
+    let mut decoder = InstructionDecoder {
+        read,
+        labels,
+        rom_bank,
+    };
+
+    (0..count)
+        .map(|_| {
+            let instruction = decoder.decode(addr);
+            addr = addr.wrapping_add(instruction.len() as u16);
+            instruction
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, Labels};
+
+    #[test]
+    fn resolves_operands_and_targets() {
+        let code = [
+            0x3E, 0x12, // LD A, $12
+            0xEA, 0x00, 0xC0, // LD ($C000), A
+            0xCB, 0x7C, // BIT 7, H
+            0x18, 0xFE, // JR -2 (to itself)
+            0xCD, 0x50, 0x01, // CALL $0150
+            0x04, // INC B
+            0xE0, 0x44, // LD ($FF44), A
+            0xD3, // illegal
+        ];
+
+        let mut labels = Labels::default();
+        labels.insert(0, 0x0150, "Main");
+
+        let result = disassemble(&code, 0x0100, Some(0), Some(&labels));
+        let mnemonics = result
+            .iter()
+            .map(|instruction| instruction.mnemonic.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            mnemonics,
+            [
+                "LD A, $12",
+                "LD ($C000), A",
+                "BIT 7, H",
+                "JR $0107",
+                "CALL Main",
+                "INC B",
+                "LD ($FF44), A",
+                "DB $D3",
+            ]
+        );
+
+        assert_eq!(result[3].target, Some(0x0107));
+        assert_eq!(result[4].target, Some(0x0150));
+        assert_eq!(result[4].len(), 3);
+        assert_eq!(result[7].addr, 0x010F);
+    }
+
+    #[test]
+    fn incomplete_instruction_at_end() {
+        let result = disassemble(&[0x00, 0xC3, 0x50], 0x0000, None, None);
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[1].mnemonic, "DB $C3");
+        assert_eq!(result[2].mnemonic, "DB $50");
+        assert_eq!(result[2].addr, 0x0002);
+    }
+
+    #[test]
+    fn parse_sym_file() {
+        let labels = Labels::from_sym(
+            "; File generated by rgblink\n00:0150 Main\n01:4000 Bank1Start ; comment\ninvalid\n",
+        );
+
+        assert_eq!(labels.get(Some(0), 0x0150), Some("Main"));
+        assert_eq!(labels.get(Some(1), 0x4000), Some("Bank1Start"));
+        assert_eq!(labels.get(Some(2), 0x4000), None);
+        assert_eq!(labels.get(None, 0x4000), Some("Bank1Start"));
+    }
+
+    #[test]
+    fn any_bank_label_is_from_lowest_bank() {
+        let mut labels = Labels::default();
+        for bank in (1..=8).rev() {
+            labels.insert(bank, 0x4000, &format!("Bank{}", bank));
+        }
+
+        assert_eq!(labels.get(None, 0x4000), Some("Bank1"));
+
+        let result = disassemble(&[0x00], 0x4000, Some(5), Some(&labels));
+        assert_eq!(result[0].label.as_deref(), Some("Bank5"));
+    }
+}
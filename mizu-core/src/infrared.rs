@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A device that communicates with the gameboy using infrared light, like
+/// another gameboy or a toy. Used by the HuC1 and HuC3 cartridges, which have
+/// an infrared LED and receiver.
+///
+/// Infrared communication is done by turning the light on and off with
+/// precise timing, so the device is given the current state of the LED and is
+/// asked about the light it emits whenever the game reads the receiver.
+pub trait InfraredDevice {
+    /// Called when the gameboy turns its LED on or off
+    fn set_light(&mut self, on: bool);
+
+    /// Returns `true` if the device is emitting light towards the gameboy
+    fn receive_light(&mut self) -> bool;
+}
+
+/// The infrared LED and receiver of a cartridge, the device is not saved in
+/// save states, and must be kept by the owner when loading a state
+#[derive(Default, Serialize, Deserialize)]
+pub struct InfraredPort {
+    light_on: bool,
+
+    #[serde(skip)]
+    device: Option<Rc<RefCell<dyn InfraredDevice>>>,
+}
+
+impl InfraredPort {
+    pub fn connect_device(&mut self, device: Option<Rc<RefCell<dyn InfraredDevice>>>) {
+        self.device = device;
+
+        // sync the current state of the LED
+        if let Some(device) = self.device.as_ref() {
+            if let Ok(mut device) = device.try_borrow_mut() {
+                device.set_light(self.light_on);
+            }
+        }
+    }
+
+    pub fn take_device(&mut self) -> Option<Rc<RefCell<dyn InfraredDevice>>> {
+        self.device.take()
+    }
+
+    /// Returns `true` if light is received, always `false` if there is no
+    /// device connected
+    pub fn receive_light(&mut self) -> bool {
+        self.device
+            .as_ref()
+            .and_then(|device| device.try_borrow_mut().ok())
+            .is_some_and(|mut device| device.receive_light())
+    }
+
+    pub fn set_light(&mut self, on: bool) {
+        if self.light_on != on {
+            self.light_on = on;
+
+            if let Some(device) = self.device.as_ref() {
+                if let Ok(mut device) = device.try_borrow_mut() {
+                    device.set_light(on);
+                }
+            }
+        }
+    }
+}
@@ -3,6 +3,7 @@ use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
 use std::convert::From;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum JoypadButton {
     Start,
     Select,
@@ -111,6 +112,10 @@ impl Joypad {
     pub fn release_joypad(&mut self, button: JoypadButton) {
         self.buttons.remove(button.into())
     }
+
+    pub fn is_pressed(&self, button: JoypadButton) -> bool {
+        self.buttons.contains(button.into())
+    }
 }
 
 impl_savable!(Joypad, 32);
@@ -3,13 +3,20 @@ mod save_state;
 
 mod apu;
 mod cartridge;
+mod cheats;
 mod cpu;
+mod debugger;
+mod disassembler;
+mod infrared;
 mod joypad;
 mod memory;
+mod movie;
 mod ppu;
 mod printer;
+mod rewind;
 mod serial;
 mod timer;
+mod trace;
 
 #[cfg(test)]
 mod tests;
@@ -17,23 +24,101 @@ mod tests;
 use std::cell::RefCell;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
+pub use cartridge::{
+    CartridgeError, CartridgeInfo, CartridgeWarning, CgbSupport, FileSramStorage, Licensee,
+    MapperType, MemorySramStorage, SramError, SramStorage, CAMERA_HEIGHT, CAMERA_WIDTH,
+};
+pub use cheats::{Cheat, CheatCode, CheatError, Cheats};
+pub use cpu::CpuRegisters;
+pub use debugger::{BreakReason, Debugger, WatchKind};
+pub use disassembler::{disassemble, disassemble_rom_bank, DisassembledInstruction, Labels};
+pub use infrared::InfraredDevice;
 pub use joypad::JoypadButton;
+pub use memory::MemoryRegion;
+pub use movie::{Movie, MovieAnchor};
 pub use printer::Printer;
+pub use save_state::{SaveError, SAVE_STATE_VERSION};
+pub use trace::{TraceEntry, TraceSink, WriterTraceSink};
 
-use cartridge::{Cartridge, CartridgeError};
+use cartridge::Cartridge;
 use cpu::Cpu;
 use memory::Bus;
+use movie::{MoviePlayer, MovieRecorder};
+use rewind::RewindBuffer;
+use save_state::{SaveStateBuilder, SaveStateReader};
 use serial::SerialDevice;
+use trace::Tracer;
+
+const PPU_CYCLES_PER_FRAME: u32 = 456 * 154;
+
+/// How to handle invalid values in the cartridge header
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderValidation {
+    /// Fail to load the cartridge, a ROM with a size that can be padded is
+    /// still loaded and reported with [`GameBoy::cartridge_warnings`]
+    #[default]
+    Strict,
+    /// Load the cartridge and report the problems with
+    /// [`GameBoy::cartridge_warnings`]
+    Warn,
+    /// Load the cartridge without reporting anything
+    Ignore,
+}
+
+impl HeaderValidation {
+    /// Returns `error` if the policy is `Strict`, otherwise `warning` is
+    /// added to `warnings` if the policy is `Warn`
+    pub(crate) fn report(
+        self,
+        warnings: &mut Vec<CartridgeWarning>,
+        error: CartridgeError,
+        warning: CartridgeWarning,
+    ) -> Result<(), CartridgeError> {
+        match self {
+            Self::Strict => Err(error),
+            Self::Warn => {
+                warnings.push(warning);
+                Ok(())
+            }
+            Self::Ignore => Ok(()),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Unknown header validation policy {0:?}, should be 'strict', 'warn' or 'ignore'")]
+pub struct ParseHeaderValidationError(String);
+
+impl FromStr for HeaderValidation {
+    type Err = ParseHeaderValidationError;
+
+    /// Parses the policy from `strict`, `warn` or `ignore`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "warn" => Ok(Self::Warn),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(ParseHeaderValidationError(s.to_string())),
+        }
+    }
+}
 
 #[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct GameboyConfig {
     /// Should the gameboy run in DMG mode? default is in CGB mode
     pub is_dmg: bool,
+    /// How to handle invalid cartridge headers, homebrew and test roms
+    /// may not have valid headers. default is `Strict`
+    ///
+    /// Only used when loading the cartridge, so it is not saved in save states
+    #[serde(skip)]
+    pub header_validation: HeaderValidation,
 }
 
 impl GameboyConfig {
@@ -46,10 +131,44 @@ impl GameboyConfig {
     }
 }
 
+/// Stored in save states to make sure they are not loaded into another game
+#[derive(Serialize, Deserialize)]
+struct SaveStateGameInfo {
+    game_title: String,
+    /// The position in the current frame, so that a save state taken in the
+    /// middle of a frame resumes with the same frame boundaries
+    frame_cycles: u32,
+}
+
+impl_savable!(SaveStateGameInfo, 64);
+
 pub struct GameBoy {
     cpu: Cpu,
     bus: Bus,
     game_title: String,
+    rewind_buffer: Option<RewindBuffer>,
+
+    /// The number of PPU cycles elapsed in the current frame, kept between
+    /// calls to `clock_for_frame` as it can stop in the middle of a frame
+    frame_cycles: u32,
+    /// The number of mapper clocks the rumble motor was on in the last
+    /// finished frame
+    rumble_cycles: u32,
+    /// The number of PPU cycles since power-on, used to timestamp the inputs
+    /// of movies
+    clock: u64,
+    movie_recorder: Option<MovieRecorder>,
+    movie_player: Option<MoviePlayer>,
+
+    /// The number of frames between automatic saves of the SRAM
+    sram_autosave_interval: Option<u32>,
+    frames_since_sram_save: u32,
+    sram_autosave_error: Option<SramError>,
+
+    cheats: Cheats,
+    /// The file the cheats are loaded from and saved to, which is next to
+    /// the ROM file, `None` if the ROM was not loaded from a file
+    cheats_file: Option<PathBuf>,
 }
 
 impl GameBoy {
@@ -58,25 +177,89 @@ impl GameBoy {
         boot_rom_file: Option<P>,
         config: GameboyConfig,
     ) -> Result<Self, CartridgeError> {
-        let cartridge = Cartridge::from_file(file_path)?;
-
-        let game_title = cartridge.game_title().to_string();
+        let cheats_file = Self::get_cheats_file(&file_path);
+        let mut cartridge = Cartridge::from_file(file_path, config.header_validation)?;
 
-        let (bus, cpu) = if let Some(boot_rom_file) = boot_rom_file {
+        let boot_rom = if let Some(boot_rom_file) = boot_rom_file {
             let mut boot_rom_file = File::open(boot_rom_file)?;
-            let mut data = vec![0; config.boot_rom_len()];
+            let boot_rom_len = boot_rom_file.metadata()?.len() as usize;
 
             // make sure the boot_rom is the exact same size
-            assert_eq!(
-                boot_rom_file.metadata()?.len(),
-                data.len() as u64,
-                "boot_rom file size is not correct"
-            );
+            if boot_rom_len != config.boot_rom_len() {
+                return Err(CartridgeError::InvalidBootRomSize {
+                    expected: config.boot_rom_len(),
+                    got: boot_rom_len,
+                });
+            }
 
+            let mut data = vec![0; boot_rom_len];
             boot_rom_file.read_exact(&mut data)?;
 
+            Some(data)
+        } else {
+            None
+        };
+
+        let cheats = Cheats::load_from_file(&cheats_file).unwrap_or_else(|err| {
+            cartridge.add_warning(CartridgeWarning::CheatsLoadFailed(err.to_string()));
+            Cheats::default()
+        });
+
+        Ok(Self::with_cartridge(
+            cartridge,
+            boot_rom,
+            config,
+            cheats,
+            Some(cheats_file),
+        ))
+    }
+
+    /// Creates a gameboy from a ROM in memory, which can also be a zip or gzip
+    /// archive containing the ROM.
+    ///
+    /// Nothing is read from or written to the filesystem, so the SRAM of
+    /// cartridges with a battery must be saved and restored by the caller
+    /// using [`set_sram_storage`](Self::set_sram_storage), or
+    /// [`sram_data`](Self::sram_data) and [`load_sram_data`](Self::load_sram_data).
+    pub fn from_bytes(
+        rom: &[u8],
+        boot_rom: Option<&[u8]>,
+        config: GameboyConfig,
+    ) -> Result<Self, CartridgeError> {
+        let cartridge = Cartridge::from_bytes(rom.to_vec(), config.header_validation)?;
+
+        if let Some(boot_rom) = boot_rom {
+            // make sure the boot_rom is the exact same size
+            if boot_rom.len() != config.boot_rom_len() {
+                return Err(CartridgeError::InvalidBootRomSize {
+                    expected: config.boot_rom_len(),
+                    got: boot_rom.len(),
+                });
+            }
+        }
+        let boot_rom = boot_rom.map(|boot_rom| boot_rom.to_vec());
+
+        Ok(Self::with_cartridge(
+            cartridge,
+            boot_rom,
+            config,
+            Cheats::default(),
+            None,
+        ))
+    }
+
+    fn with_cartridge(
+        cartridge: Cartridge,
+        boot_rom: Option<Vec<u8>>,
+        config: GameboyConfig,
+        cheats: Cheats,
+        cheats_file: Option<PathBuf>,
+    ) -> Self {
+        let game_title = cartridge.game_title().to_string();
+
+        let (bus, cpu) = if let Some(boot_rom) = boot_rom {
             (
-                Bus::new_with_boot_rom(cartridge, data, config),
+                Bus::new_with_boot_rom(cartridge, boot_rom, config),
                 Cpu::new(config),
             )
         } else {
@@ -87,11 +270,35 @@ impl GameBoy {
             )
         };
 
-        Ok(Self {
+        let mut s = Self {
             bus,
             cpu,
             game_title,
-        })
+            rewind_buffer: None,
+            frame_cycles: 0,
+            rumble_cycles: 0,
+            clock: 0,
+            movie_recorder: None,
+            movie_player: None,
+            sram_autosave_interval: None,
+            frames_since_sram_save: 0,
+            sram_autosave_error: None,
+            cheats,
+            cheats_file,
+        };
+        s.update_rom_cheats();
+
+        s
+    }
+
+    fn get_cheats_file<P: AsRef<Path>>(path: P) -> PathBuf {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        path.as_ref()
+            .with_extension(format!("{}.cheats", extension))
     }
 
     /// Synced to PPU
@@ -100,12 +307,130 @@ impl GameBoy {
     /// number of PPU cycles per frame is fixed, counting for the number
     /// of ppu cycles is better than waiting for Vblank, as if the lcd
     /// is off, Vblank is not coming
-    pub fn clock_for_frame(&mut self) {
-        const PPU_CYCLES_PER_FRAME: u32 = 456 * 154;
-        let mut cycles = 0u32;
-        while cycles < PPU_CYCLES_PER_FRAME {
-            self.cpu.next_instruction(&mut self.bus);
-            cycles += self.bus.elapsed_ppu_cycles() as u32;
+    ///
+    /// Stops early if a breakpoint or a watchpoint of the [`Debugger`] is hit,
+    /// or if the CPU hangs because of an illegal opcode. The next call will
+    /// continue the rest of the frame.
+    pub fn clock_for_frame(&mut self) -> Option<BreakReason> {
+        // GameShark cheats are applied once at the start of every frame
+        if self.frame_cycles == 0 {
+            self.apply_ram_cheats();
+        }
+
+        loop {
+            match self.clock_instruction() {
+                Ok(true) => return None,
+                Ok(false) => {}
+                Err(reason) => return Some(reason),
+            }
+        }
+    }
+
+    /// Executes one instruction, returns `true` if the frame is finished
+    /// after this instruction.
+    ///
+    /// If a breakpoint is at the current instruction, it is not executed.
+    fn clock_instruction(&mut self) -> Result<bool, BreakReason> {
+        let pc = self.cpu.registers().pc;
+        if let Some(reason) = self.bus.debugger().check_breakpoint(pc) {
+            return Err(reason);
+        }
+
+        if let Some(movie_player) = self.movie_player.as_mut() {
+            while let Some((button, pressed)) = movie_player.next_event(self.clock) {
+                if pressed {
+                    self.bus.press_joypad(button);
+                } else {
+                    self.bus.release_joypad(button);
+                }
+            }
+        }
+
+        let was_locked = self.cpu.locked_at().is_some();
+
+        self.cpu.next_instruction(&mut self.bus);
+        let elapsed = self.bus.elapsed_ppu_cycles();
+        self.frame_cycles += elapsed;
+        self.clock += elapsed as u64;
+
+        let frame_finished = self.frame_cycles >= PPU_CYCLES_PER_FRAME;
+        if frame_finished {
+            self.frame_cycles = 0;
+            self.on_frame_finished();
+        }
+
+        let watchpoint_hit = self.bus.debugger().take_watchpoint_hit();
+
+        if !was_locked {
+            if let Some(addr) = self.cpu.locked_at() {
+                return Err(BreakReason::CpuLocked(addr));
+            }
+        }
+
+        match watchpoint_hit {
+            Some(reason) => Err(reason),
+            None => Ok(frame_finished),
+        }
+    }
+
+    fn on_frame_finished(&mut self) {
+        self.rumble_cycles = self.bus.cartridge_mut().take_rumble_cycles();
+
+        let movie_finished = self
+            .movie_player
+            .as_mut()
+            .is_some_and(|movie_player| movie_player.is_finished());
+
+        if movie_finished {
+            self.movie_player = None;
+        }
+
+        let take_rewind_state = self
+            .rewind_buffer
+            .as_mut()
+            .is_some_and(|rewind_buffer| rewind_buffer.frame_finished());
+
+        if take_rewind_state {
+            if let Ok(state) = self.save_state() {
+                self.rewind_buffer.as_mut().unwrap().push(state);
+            }
+        }
+
+        if let Some(interval) = self.sram_autosave_interval {
+            self.frames_since_sram_save += 1;
+
+            if self.frames_since_sram_save >= interval {
+                self.frames_since_sram_save = 0;
+
+                if self.bus.cartridge().is_sram_dirty() {
+                    if let Err(err) = self.flush_sram() {
+                        self.sram_autosave_error = Some(err);
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_rom_cheats(&mut self) {
+        self.bus.set_rom_patches(self.cheats.rom_patches());
+    }
+
+    fn apply_ram_cheats(&mut self) {
+        for (addr, value, bank) in self.cheats.ram_patches() {
+            match (bank, addr) {
+                (Some(bank), 0xA000..=0xBFFF) => {
+                    let offset = (addr - 0xA000) as usize;
+                    self.bus
+                        .write_banked(MemoryRegion::Sram, bank as usize, offset, value);
+                }
+                (Some(bank), 0xD000..=0xDFFF) => {
+                    // bank 0 cannot be mapped to 0xD000, it maps bank 1 instead
+                    let offset = (addr & 0xFFF) as usize;
+                    self.bus
+                        .write_banked(MemoryRegion::Wram, bank.max(1) as usize, offset, value);
+                }
+                _ => self.bus.debug_write(addr, value),
+            }
         }
     }
 
@@ -121,11 +446,36 @@ impl GameBoy {
         self.bus.audio_buffer()
     }
 
+    pub fn cpu_registers(&self) -> CpuRegisters {
+        self.cpu.registers()
+    }
+
+    /// Returns the address of the illegal opcode that hung the CPU, if any
+    pub fn cpu_locked_at(&self) -> Option<u16> {
+        self.cpu.locked_at()
+    }
+
+    /// Ignored while a movie is being played
     pub fn press_joypad(&mut self, button: JoypadButton) {
+        if self.movie_player.is_some() {
+            return;
+        }
+
+        if let Some(movie_recorder) = self.movie_recorder.as_mut() {
+            movie_recorder.record(self.clock, button, true);
+        }
         self.bus.press_joypad(button);
     }
 
+    /// Ignored while a movie is being played
     pub fn release_joypad(&mut self, button: JoypadButton) {
+        if self.movie_player.is_some() {
+            return;
+        }
+
+        if let Some(movie_recorder) = self.movie_recorder.as_mut() {
+            movie_recorder.record(self.clock, button, false);
+        }
         self.bus.release_joypad(button);
     }
 
@@ -138,4 +488,512 @@ impl GameBoy {
     pub fn disconnect_device(&mut self) {
         self.bus.disconnect_device();
     }
+
+    /// Connects a device to the infrared port of the cartridge, only HuC1
+    /// and HuC3 cartridges have one, for other cartridges this does nothing
+    pub fn connect_infrared_device(&mut self, device: Rc<RefCell<dyn InfraredDevice>>) {
+        self.bus
+            .cartridge_mut()
+            .connect_infrared_device(Some(device));
+    }
+
+    /// Disconnects the infrared device if any is connected
+    pub fn disconnect_infrared_device(&mut self) {
+        self.bus.cartridge_mut().connect_infrared_device(None);
+    }
+
+    /// Creates a snapshot of the whole emulation state, which can be loaded
+    /// later with [`load_state`](Self::load_state), even in the middle of a frame.
+    pub fn save_state(&self) -> Result<Vec<u8>, SaveError> {
+        let mut state = SaveStateBuilder::default();
+
+        state.add(
+            *b"GAME",
+            &SaveStateGameInfo {
+                game_title: self.game_title.clone(),
+                frame_cycles: self.frame_cycles,
+            },
+        )?;
+        state.add(*b"CPU ", &self.cpu)?;
+        self.bus.save_state(&mut state)?;
+
+        Ok(state.finish())
+    }
+
+    /// Restores the emulation state from a snapshot created by
+    /// [`save_state`](Self::save_state).
+    ///
+    /// The header and the sections of the snapshot are validated before any
+    /// state is modified, and if an object fails to load afterwards, the
+    /// previous state is restored, so an invalid save state does not leave
+    /// the emulator half-restored.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveError> {
+        let state = SaveStateReader::new(data)?;
+
+        let mut game_info = SaveStateGameInfo {
+            game_title: String::new(),
+            frame_cycles: 0,
+        };
+        state.load(*b"GAME", &mut game_info)?;
+
+        if game_info.game_title != self.game_title {
+            return Err(SaveError::GameMismatch(game_info.game_title));
+        }
+
+        state.check::<Cpu>(*b"CPU ")?;
+        self.bus.check_state(&state)?;
+
+        // kept to go back to it if any object fails to load
+        let previous_state = self.save_state()?;
+
+        if let Err(err) = self.load_state_sections(&state) {
+            self.load_state_sections(&SaveStateReader::new(&previous_state)?)?;
+            return Err(err);
+        }
+
+        self.frame_cycles = game_info.frame_cycles;
+
+        Ok(())
+    }
+
+    fn load_state_sections(&mut self, state: &SaveStateReader) -> Result<(), SaveError> {
+        state.load(*b"CPU ", &mut self.cpu)?;
+        self.bus.load_state(state)
+    }
+
+    /// Starts recording save states to be able to [`rewind`](Self::rewind),
+    /// a save state is recorded every `interval_frames` frames.
+    ///
+    /// `memory_budget` is the maximum number of bytes to use for the history,
+    /// the oldest save states are dropped when it is exceeded.
+    pub fn enable_rewind(
+        &mut self,
+        interval_frames: u32,
+        memory_budget: usize,
+    ) -> Result<(), SaveError> {
+        let state = self.save_state()?;
+        self.rewind_buffer = Some(RewindBuffer::new(interval_frames, memory_budget, state));
+
+        Ok(())
+    }
+
+    /// Stops recording save states and drops the rewind history
+    pub fn disable_rewind(&mut self) {
+        self.rewind_buffer = None;
+    }
+
+    /// Goes back in time by at least `frames` frames, or to the oldest
+    /// recorded point if the history is shorter than that.
+    ///
+    /// Returns the number of frames actually rewound, which is `0` if rewind
+    /// is not enabled.
+    pub fn rewind(&mut self, frames: u32) -> Result<u32, SaveError> {
+        if frames == 0 {
+            return Ok(0);
+        }
+
+        // take the buffer out, as loading the state needs `self`
+        let mut rewind_buffer = match self.rewind_buffer.take() {
+            Some(rewind_buffer) => rewind_buffer,
+            None => return Ok(0),
+        };
+
+        let (state, rewound_frames) = rewind_buffer.rewind(frames);
+        let result = self.load_state(state);
+
+        self.rewind_buffer = Some(rewind_buffer);
+
+        result.map(|_| rewound_frames)
+    }
+
+    /// Starts recording the joypad inputs into a [`Movie`], which is anchored
+    /// to power-on if no frame was emulated yet, or to a save state of the
+    /// current point otherwise.
+    ///
+    /// Loading a save state or rewinding while recording will make the movie
+    /// not reproduce the run.
+    pub fn start_movie_recording(&mut self) -> Result<(), SaveError> {
+        let anchor = if self.clock == 0 {
+            MovieAnchor::PowerOn
+        } else {
+            MovieAnchor::SaveState
+        };
+
+        let mut movie_recorder = MovieRecorder::new(
+            self.clock,
+            self.game_title.clone(),
+            anchor,
+            self.save_state()?,
+        );
+
+        // buttons pressed are not part of the save state, so they are
+        // recorded as pressed at the start of the movie
+        for &button in &[
+            JoypadButton::Start,
+            JoypadButton::Select,
+            JoypadButton::B,
+            JoypadButton::A,
+            JoypadButton::Down,
+            JoypadButton::Up,
+            JoypadButton::Left,
+            JoypadButton::Right,
+        ] {
+            if self.bus.is_joypad_pressed(button) {
+                movie_recorder.record(self.clock, button, true);
+            }
+        }
+
+        self.movie_player = None;
+        self.movie_recorder = Some(movie_recorder);
+
+        Ok(())
+    }
+
+    /// Stops recording and returns the recorded movie, or `None` if
+    /// no recording is in progress
+    pub fn stop_movie_recording(&mut self) -> Option<Movie> {
+        self.movie_recorder
+            .take()
+            .map(|movie_recorder| movie_recorder.finish())
+    }
+
+    /// Loads the start state of the movie and plays back its inputs, the
+    /// joypad inputs from [`press_joypad`](Self::press_joypad) and
+    /// [`release_joypad`](Self::release_joypad) are ignored until the movie
+    /// is finished or [`stop_movie_playback`](Self::stop_movie_playback) is called.
+    pub fn play_movie(&mut self, movie: &Movie) -> Result<(), SaveError> {
+        self.load_state(movie.start_state())?;
+
+        self.movie_recorder = None;
+        self.movie_player = Some(MoviePlayer::new(self.clock, movie));
+
+        Ok(())
+    }
+
+    pub fn stop_movie_playback(&mut self) {
+        self.movie_player = None;
+    }
+
+    /// Returns `true` if a movie is being played, the playback stops at the
+    /// end of the frame where the last input of the movie is applied
+    pub fn is_playing_movie(&self) -> bool {
+        self.movie_player.is_some()
+    }
+
+    /// Sets the sink that receives the CPU state before every instruction,
+    /// `None` disables tracing. Returns the old sink if any.
+    ///
+    /// The cycles in the trace entries are counted from the time the sink is set.
+    pub fn set_trace_sink(
+        &mut self,
+        sink: Option<Box<dyn TraceSink>>,
+    ) -> Option<Box<dyn TraceSink>> {
+        self.bus
+            .set_tracer(sink.map(Tracer::new))
+            .map(|tracer| tracer.into_sink())
+    }
+
+    /// Breakpoints and watchpoints used by [`clock_for_frame`](Self::clock_for_frame)
+    /// and the stepping functions
+    pub fn debugger(&mut self) -> &mut Debugger {
+        self.bus.debugger()
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        self.cheats.cheats()
+    }
+
+    /// Adds an enabled Game Genie or GameShark cheat, and returns its index.
+    ///
+    /// Game Genie cheats patch the ROM when it is read, GameShark cheats
+    /// write to RAM at the start of every frame.
+    pub fn add_cheat(&mut self, code: &str, name: &str) -> Result<usize, CheatError> {
+        let index = self.cheats.add(code, name)?;
+        self.update_rom_cheats();
+
+        Ok(index)
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) -> Option<Cheat> {
+        let cheat = self.cheats.remove(index);
+        self.update_rom_cheats();
+
+        cheat
+    }
+
+    /// Returns `false` if there is no cheat with this index
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let found = self.cheats.set_enabled(index, enabled);
+        self.update_rom_cheats();
+
+        found
+    }
+
+    pub fn clear_cheats(&mut self) {
+        self.cheats.clear();
+        self.update_rom_cheats();
+    }
+
+    /// Saves the cheats to a file next to the ROM file (`<rom>.gb.cheats`),
+    /// which is loaded automatically the next time the ROM is loaded.
+    ///
+    /// Fails with [`CheatError::NoCheatsFile`] if the ROM was loaded with
+    /// [`from_bytes`](Self::from_bytes), in that case the caller should store
+    /// the [`cheats`](Self::cheats) itself.
+    pub fn save_cheats(&self) -> Result<(), CheatError> {
+        let cheats_file = self.cheats_file.as_ref().ok_or(CheatError::NoCheatsFile)?;
+
+        self.cheats.save_to_file(cheats_file)
+    }
+
+    /// The information in the header of the loaded cartridge, use
+    /// [`CartridgeInfo::from_bytes`] to get it without loading the game
+    pub fn cartridge_info(&self) -> &CartridgeInfo {
+        self.bus.cartridge().info()
+    }
+
+    /// Problems found while loading the cartridge, its save file and its
+    /// cheats file, see [`HeaderValidation`]
+    pub fn cartridge_warnings(&self) -> &[CartridgeWarning] {
+        self.bus.cartridge().warnings()
+    }
+
+    /// Sets the tilt of the cartridge for games with an accelerometer
+    /// (MBC7), like `Kirby Tilt 'n' Tumble`. The values are in units of g,
+    /// where `1.0` is about 90 degrees: positive `x` is tilting the right
+    /// side down, and positive `y` is tilting the bottom side down.
+    /// The game reads the last values set, so this should be called every
+    /// frame while the tilt is changing.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.bus.cartridge_mut().set_tilt(x, y);
+    }
+
+    /// Returns `true` if the rumble motor of the cartridge is on (MBC5 with
+    /// rumble, like `Pokemon Pinball`), always `false` for other cartridges.
+    ///
+    /// This is the state at the time of the call, games control the strength
+    /// of the motor by turning it on and off many times per frame, so
+    /// frontends should use [`GameBoy::rumble_cycles`] to drive force
+    /// feedback.
+    pub fn rumble_active(&self) -> bool {
+        self.bus.cartridge().rumble_active()
+    }
+
+    /// Returns the number of mapper clocks the rumble motor was on in the
+    /// last finished frame, out of the `35112` mapper clocks of a frame in
+    /// both normal and double speed, which gives the strength of the rumble.
+    pub fn rumble_cycles(&self) -> u32 {
+        self.rumble_cycles
+    }
+
+    /// Sets the image in front of the sensor of the Game Boy Camera, `frame`
+    /// is [`CAMERA_WIDTH`] x [`CAMERA_HEIGHT`] greyscale pixels (row by row),
+    /// from `0` (black) to `255` (white). The last frame set is used for all
+    /// the following captures, so for video this should be called every frame.
+    ///
+    /// Does nothing for other cartridges.
+    ///
+    /// # Panics
+    ///
+    /// If the size of `frame` is not `CAMERA_WIDTH * CAMERA_HEIGHT`
+    pub fn set_camera_frame(&mut self, frame: &[u8]) {
+        assert_eq!(
+            frame.len(),
+            CAMERA_WIDTH * CAMERA_HEIGHT,
+            "the camera frame must be {}x{}",
+            CAMERA_WIDTH,
+            CAMERA_HEIGHT
+        );
+
+        self.bus.cartridge_mut().set_camera_frame(frame);
+    }
+
+    /// Returns `true` if the cartridge has a battery, and thus its SRAM
+    /// should be saved
+    pub fn has_battery(&self) -> bool {
+        self.bus.cartridge().has_battery()
+    }
+
+    /// The content of the battery backed SRAM (followed by the RTC data if
+    /// the cartridge has one), in the same format of the `.sav` files.
+    /// Returns `None` if the cartridge does not have a battery.
+    pub fn sram_data(&self) -> Option<Vec<u8>> {
+        let cartridge = self.bus.cartridge();
+
+        if cartridge.has_battery() {
+            Some(cartridge.save_battery())
+        } else {
+            None
+        }
+    }
+
+    /// Loads SRAM data saved with [`sram_data`](Self::sram_data)
+    pub fn load_sram_data(&mut self, data: &[u8]) -> Result<(), SramError> {
+        self.bus.cartridge_mut().load_battery(data)
+    }
+
+    /// Replaces where the SRAM is saved, and loads the SRAM from `storage`
+    /// if it contains saved data. This should be called before starting the
+    /// emulation.
+    ///
+    /// By default, [`new`](Self::new) saves the SRAM to a file next to the
+    /// ROM file, and [`from_bytes`](Self::from_bytes) does not save it.
+    /// The SRAM is saved when the gameboy is dropped, when calling
+    /// [`flush_sram`](Self::flush_sram), and periodically if
+    /// [`set_sram_autosave`](Self::set_sram_autosave) is used.
+    pub fn set_sram_storage(
+        &mut self,
+        storage: Option<Box<dyn SramStorage>>,
+    ) -> Result<(), SramError> {
+        self.bus.cartridge_mut().set_sram_storage(storage)
+    }
+
+    /// Saves the SRAM to the storage now, does nothing if the cartridge
+    /// does not have a battery or if there is no storage
+    pub fn flush_sram(&mut self) -> Result<(), SramError> {
+        self.bus.cartridge_mut().flush_sram()
+    }
+
+    /// Saves the SRAM every `interval` frames if it was modified, so that
+    /// progress is not lost if the program crashes. `None` disables the
+    /// automatic saving.
+    pub fn set_sram_autosave(&mut self, interval: Option<u32>) {
+        self.sram_autosave_interval = interval.filter(|&interval| interval > 0);
+        self.frames_since_sram_save = 0;
+    }
+
+    /// Returns the error of the last failed automatic save of the SRAM if
+    /// any, and clears it
+    pub fn take_sram_autosave_error(&mut self) -> Option<SramError> {
+        self.sram_autosave_error.take()
+    }
+
+    /// Reads memory the same way the CPU does, but without clocking the
+    /// components or triggering watchpoints
+    pub fn peek(&mut self, addr: u16) -> u8 {
+        self.bus.debug_read(addr)
+    }
+
+    /// Writes memory the same way the CPU does, but without clocking the
+    /// components or triggering watchpoints.
+    ///
+    /// Writing to `0x0000-0x7FFF` writes to the mapper registers and not the
+    /// ROM, and writing to OAM while the PPU is using it is ignored, use
+    /// [`poke_banked`](Self::poke_banked) to write to them directly.
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        self.bus.debug_write(addr, value);
+    }
+
+    /// The number of banks in `region`, the ROM and SRAM banks depend on the
+    /// cartridge, and the WRAM and VRAM banks depend on the mode (DMG or CGB)
+    pub fn bank_count(&self, region: MemoryRegion) -> usize {
+        self.bus.bank_count(region)
+    }
+
+    /// Reads `offset` in `bank` of `region` regardless of the banks currently
+    /// mapped, returns `None` if the bank or the offset is out of range.
+    ///
+    /// Offsets start from the beginning of the region, for example
+    /// `peek_banked(MemoryRegion::Io, 0, 0x44)` reads `LY` (`0xFF44`).
+    pub fn peek_banked(&mut self, region: MemoryRegion, bank: usize, offset: usize) -> Option<u8> {
+        self.bus.read_banked(region, bank, offset)
+    }
+
+    /// Writes `value` to `offset` in `bank` of `region` regardless of the
+    /// banks currently mapped and the PPU locks, returns `false` if the bank
+    /// or the offset is out of range.
+    ///
+    /// Writing to ROM modifies the loaded ROM data, not the mapper registers.
+    pub fn poke_banked(
+        &mut self,
+        region: MemoryRegion,
+        bank: usize,
+        offset: usize,
+        value: u8,
+    ) -> bool {
+        self.bus.write_banked(region, bank, offset, value)
+    }
+
+    /// Executes a single instruction, even if there is a breakpoint on it.
+    ///
+    /// Returns the watchpoint hit by the instruction if any.
+    pub fn step(&mut self) -> Option<BreakReason> {
+        self.bus.debugger().skip_next_breakpoint();
+        self.clock_instruction().err()
+    }
+
+    /// Same as [`step`](Self::step), but if the instruction is a `call` or `rst`,
+    /// runs until it returns.
+    ///
+    /// Stops early if a breakpoint or a watchpoint is hit. Note that if the
+    /// function never returns, this will not return either.
+    pub fn step_over(&mut self) -> Option<BreakReason> {
+        let registers = self.cpu.registers();
+        let opcode = self.bus.debug_read(registers.pc);
+
+        let instruction_len = match opcode {
+            // call, call cc
+            0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC => 3,
+            // rst
+            _ if opcode & 0xC7 == 0xC7 => 1,
+            _ => return self.step(),
+        };
+        let return_addr = registers.pc.wrapping_add(instruction_len);
+
+        self.step()?;
+
+        loop {
+            let current = self.cpu.registers();
+            // the stack pointer is checked for recursive functions
+            if current.pc == return_addr && current.sp >= registers.sp {
+                return None;
+            }
+
+            if let Err(reason) = self.clock_instruction() {
+                return Some(reason);
+            }
+        }
+    }
+
+    /// Runs until the PPU starts drawing the scanline `line` (`LY` becomes `line`).
+    ///
+    /// Stops early if a breakpoint or a watchpoint is hit, and stops after a
+    /// full frame if the scanline was not reached, which happens when the LCD
+    /// is off or `line` is larger than `153`.
+    pub fn run_to_scanline(&mut self, line: u8) -> Option<BreakReason> {
+        let mut cycles = 0;
+        let mut last_line = self.bus.current_scanline();
+
+        while cycles < PPU_CYCLES_PER_FRAME {
+            let old_clock = self.clock;
+            if let Err(reason) = self.clock_instruction() {
+                return Some(reason);
+            }
+            cycles += (self.clock - old_clock) as u32;
+
+            let current_line = self.bus.current_scanline();
+            if current_line == line && last_line != line {
+                break;
+            }
+            last_line = current_line;
+        }
+
+        None
+    }
+
+    /// Disassembles `count` instructions from the memory starting at `addr`,
+    /// the memory is read without affecting the emulation.
+    ///
+    /// Labels of `0x4000-0x7FFF` are taken from the ROM bank currently mapped
+    /// there.
+    pub fn disassemble(
+        &mut self,
+        addr: u16,
+        count: usize,
+        labels: Option<&Labels>,
+    ) -> Vec<DisassembledInstruction> {
+        let rom_bank = self.bus.cartridge().current_rom_bank();
+        let bus = &mut self.bus;
+        disassembler::disassemble_with(|addr| bus.debug_read(addr), addr, count, rom_bank, labels)
+    }
 }
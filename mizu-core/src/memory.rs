@@ -11,12 +11,15 @@ pub use interrupts::{InterruptManager, InterruptType};
 
 use crate::apu::Apu;
 use crate::cartridge::Cartridge;
-use crate::cpu::CpuBusProvider;
+use crate::cheats::RomPatch;
+use crate::cpu::{CpuBusProvider, CpuRegisters};
+use crate::debugger::Debugger;
 use crate::joypad::{Joypad, JoypadButton};
 use crate::ppu::Ppu;
-use crate::save_state::{Savable, SaveError};
+use crate::save_state::{Savable, SaveError, SaveStateBuilder, SaveStateReader};
 use crate::serial::{Serial, SerialDevice};
 use crate::timer::Timer;
+use crate::trace::Tracer;
 use crate::GameboyConfig;
 use dma::{BusType, Hdma, OamDma};
 use interrupts::Interrupts;
@@ -86,6 +89,41 @@ impl SpeedController {
 
 impl_savable!(SpeedController, 32);
 
+/// A memory region that can be accessed bank by bank, regardless of the
+/// banks currently mapped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    /// The cartridge ROM, 0x4000 bytes per bank
+    Rom,
+    /// The cartridge RAM, 0x2000 bytes per bank
+    Sram,
+    /// Work RAM, 0x1000 bytes per bank, 2 banks in DMG and 8 in CGB
+    Wram,
+    /// Video RAM, 0x2000 bytes per bank, 1 bank in DMG and 2 in CGB
+    Vram,
+    /// Sprites attributes, 0xA0 bytes
+    Oam,
+    /// High RAM, 0x7F bytes
+    Hram,
+    /// IO registers (0xFF00-0xFF7F), 0x80 bytes, accessing them has the same
+    /// effect as accessing them from the CPU
+    Io,
+}
+
+impl MemoryRegion {
+    pub fn bank_size(self) -> usize {
+        match self {
+            MemoryRegion::Rom => 0x4000,
+            MemoryRegion::Sram => 0x2000,
+            MemoryRegion::Wram => 0x1000,
+            MemoryRegion::Vram => 0x2000,
+            MemoryRegion::Oam => 0xA0,
+            MemoryRegion::Hram => 0x7F,
+            MemoryRegion::Io => 0x80,
+        }
+    }
+}
+
 struct Wram {
     data: [u8; 0x8000],
     bank: u8,
@@ -275,11 +313,16 @@ pub struct Bus {
     /// when the frontend gets the elapsed value, its reset to 0
     elapsed_ppu_cycles: u32,
 
+    debugger: Debugger,
+    tracer: Option<Tracer>,
+
     config: GameboyConfig,
 }
 
 impl Bus {
-    pub fn new_without_boot_rom(cartridge: Cartridge, config: GameboyConfig) -> Self {
+    pub fn new_without_boot_rom(mut cartridge: Cartridge, config: GameboyConfig) -> Self {
+        cartridge.skip_boot_rom();
+
         let cgb_mode = cartridge.is_cartridge_color();
         let mut lock = Lock::default();
 
@@ -313,6 +356,9 @@ impl Bus {
 
             elapsed_ppu_cycles: 0,
 
+            debugger: Debugger::default(),
+            tracer: None,
+
             config,
         }
     }
@@ -368,6 +414,10 @@ impl Bus {
         self.joypad.release_joypad(button);
     }
 
+    pub fn is_joypad_pressed(&self, button: JoypadButton) -> bool {
+        self.joypad.is_pressed(button)
+    }
+
     pub fn connect_device(&mut self, device: Rc<RefCell<dyn SerialDevice>>) {
         self.serial_device = Some(device);
     }
@@ -376,9 +426,187 @@ impl Bus {
         self.serial_device = None;
     }
 
+    pub fn debugger(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    /// Reads memory without clocking the components or triggering watchpoints
+    pub fn debug_read(&mut self, addr: u16) -> u8 {
+        self.debugger.set_muted(true);
+        let result = self.read_not_ticked(addr, None);
+        self.debugger.set_muted(false);
+
+        result
+    }
+
+    /// Replaces the tracer and returns the old one
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.tracer, tracer)
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cartridge
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cartridge
+    }
+
+    pub fn set_rom_patches(&mut self, rom_patches: Vec<RomPatch>) {
+        self.cartridge.set_rom_patches(rom_patches);
+    }
+
+    /// Writes memory without clocking the components or triggering watchpoints
+    pub fn debug_write(&mut self, addr: u16, data: u8) {
+        self.debugger.set_muted(true);
+        self.write_not_ticked(addr, data, None);
+        self.debugger.set_muted(false);
+    }
+
+    pub fn bank_count(&self, region: MemoryRegion) -> usize {
+        match region {
+            MemoryRegion::Rom => self.cartridge.rom_len().div_ceil(region.bank_size()),
+            MemoryRegion::Sram => self.cartridge.ram_len().div_ceil(region.bank_size()),
+            MemoryRegion::Wram if self.config.is_dmg => 2,
+            MemoryRegion::Wram => 8,
+            MemoryRegion::Vram if self.config.is_dmg => 1,
+            MemoryRegion::Vram => 2,
+            MemoryRegion::Oam | MemoryRegion::Hram | MemoryRegion::Io => 1,
+        }
+    }
+
+    /// Reads from a bank of a memory region without clocking the components,
+    /// returns `None` if the bank or the offset is out of range
+    pub fn read_banked(&mut self, region: MemoryRegion, bank: usize, offset: usize) -> Option<u8> {
+        if bank >= self.bank_count(region) || offset >= region.bank_size() {
+            return None;
+        }
+        let index = bank * region.bank_size() + offset;
+
+        match region {
+            MemoryRegion::Rom => self.cartridge.rom().get(index).copied(),
+            MemoryRegion::Sram => self.cartridge.ram().get(index).copied(),
+            MemoryRegion::Wram => Some(self.wram.data[index]),
+            MemoryRegion::Vram => Some(self.ppu.read_vram_banked(bank as u8, offset as u16)),
+            MemoryRegion::Oam => Some(self.ppu.read_oam_no_lock(offset as u16)),
+            MemoryRegion::Hram => Some(self.hram[offset]),
+            MemoryRegion::Io => Some(self.read_io(offset as u8)),
+        }
+    }
+
+    /// Writes to a bank of a memory region without clocking the components,
+    /// returns `false` if the bank or the offset is out of range
+    pub fn write_banked(
+        &mut self,
+        region: MemoryRegion,
+        bank: usize,
+        offset: usize,
+        data: u8,
+    ) -> bool {
+        if bank >= self.bank_count(region) || offset >= region.bank_size() {
+            return false;
+        }
+        let index = bank * region.bank_size() + offset;
+
+        match region {
+            MemoryRegion::Rom => match self.cartridge.rom_mut().get_mut(index) {
+                Some(value) => *value = data,
+                None => return false,
+            },
+            MemoryRegion::Sram => match self.cartridge.ram_mut().get_mut(index) {
+                Some(value) => *value = data,
+                None => return false,
+            },
+            MemoryRegion::Wram => self.wram.data[index] = data,
+            MemoryRegion::Vram => self.ppu.write_vram_banked(bank as u8, offset as u16, data),
+            MemoryRegion::Oam => self.ppu.write_oam_no_lock(offset as u16, data),
+            MemoryRegion::Hram => self.hram[offset] = data,
+            MemoryRegion::Io => self.write_io(offset as u8, data),
+        }
+
+        true
+    }
+
+    pub fn current_scanline(&self) -> u8 {
+        self.ppu.read_ly()
+    }
+
     pub fn elapsed_ppu_cycles(&mut self) -> u32 {
         std::mem::replace(&mut self.elapsed_ppu_cycles, 0)
     }
+
+    /// Adds all components of the bus as sections into the save `state`
+    pub fn save_state(&self, state: &mut SaveStateBuilder) -> Result<(), SaveError> {
+        state.add(*b"BUS ", self)?;
+        state.add(*b"CART", &self.cartridge)?;
+        if let Some(flash) = self.cartridge.flash() {
+            state.add_raw(*b"FLSH", flash)?;
+        }
+        state.add(*b"PPU ", &self.ppu)?;
+        state.add(*b"APU ", &self.apu)?;
+        state.add(*b"WRAM", &self.wram)?;
+        state.add(*b"INTR", &self.interrupts)?;
+        state.add(*b"TIMR", &self.timer)?;
+        state.add(*b"JOYP", &self.joypad)?;
+        state.add(*b"SERL", &self.serial)?;
+        state.add(*b"ODMA", &self.oam_dma)?;
+        state.add(*b"HDMA", &self.hdma)?;
+        state.add(*b"BROM", &self.boot_rom)?;
+        state.add(*b"SPED", &self.speed_controller)?;
+        state.add(*b"LOCK", &self.lock)?;
+        state.add(*b"UNKR", &self.unknown_registers)?;
+
+        Ok(())
+    }
+
+    /// Checks that all sections needed by [`load_state`] are present in `state`
+    pub fn check_state(&self, state: &SaveStateReader) -> Result<(), SaveError> {
+        state.check::<Self>(*b"BUS ")?;
+        state.check::<Cartridge>(*b"CART")?;
+        if let Some(flash) = self.cartridge.flash() {
+            state.check_raw(*b"FLSH", flash.len())?;
+        }
+        state.check::<Ppu>(*b"PPU ")?;
+        state.check::<Apu>(*b"APU ")?;
+        state.check::<Wram>(*b"WRAM")?;
+        state.check::<Interrupts>(*b"INTR")?;
+        state.check::<Timer>(*b"TIMR")?;
+        state.check::<Joypad>(*b"JOYP")?;
+        state.check::<Serial>(*b"SERL")?;
+        state.check::<OamDma>(*b"ODMA")?;
+        state.check::<Hdma>(*b"HDMA")?;
+        state.check::<BootRom>(*b"BROM")?;
+        state.check::<SpeedController>(*b"SPED")?;
+        state.check::<Lock>(*b"LOCK")?;
+        state.check::<UnknownRegisters>(*b"UNKR")?;
+
+        Ok(())
+    }
+
+    /// Loads all components of the bus from the save `state`, the connected
+    /// serial device is kept as is
+    pub fn load_state(&mut self, state: &SaveStateReader) -> Result<(), SaveError> {
+        state.load(*b"BUS ", self)?;
+        state.load(*b"CART", &mut self.cartridge)?;
+        if let Some(flash) = self.cartridge.flash_mut() {
+            state.load_raw(*b"FLSH", flash)?;
+        }
+        state.load(*b"PPU ", &mut self.ppu)?;
+        state.load(*b"APU ", &mut self.apu)?;
+        state.load(*b"WRAM", &mut self.wram)?;
+        state.load(*b"INTR", &mut self.interrupts)?;
+        state.load(*b"TIMR", &mut self.timer)?;
+        state.load(*b"JOYP", &mut self.joypad)?;
+        state.load(*b"SERL", &mut self.serial)?;
+        state.load(*b"ODMA", &mut self.oam_dma)?;
+        state.load(*b"HDMA", &mut self.hdma)?;
+        state.load(*b"BROM", &mut self.boot_rom)?;
+        state.load(*b"SPED", &mut self.speed_controller)?;
+        state.load(*b"LOCK", &mut self.lock)?;
+        state.load(*b"UNKR", &mut self.unknown_registers)?;
+
+        Ok(())
+    }
 }
 
 impl Bus {
@@ -395,6 +623,10 @@ impl Bus {
         // after every cpu exeution)
         self.elapsed_ppu_cycles = self.elapsed_ppu_cycles.saturating_add(t_clocks as u32);
 
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.add_cycles(t_clocks as u64);
+        }
+
         // we return after updating `elapsed_ppu_cycles` because frontend
         // depend on it
         if self.stopped {
@@ -471,7 +703,7 @@ impl Bus {
         let page = (addr >> 8) as u8;
         let offset = addr as u8;
 
-        match (page, block_for_dma) {
+        let result = match (page, block_for_dma) {
             (0x00, _) | (0x02..=0x08, _) if self.boot_rom.enabled => {
                 self.boot_rom.data[addr as usize]
             } // boot rom
@@ -492,13 +724,23 @@ impl Bus {
             (0xFE, _) if offset >= 0xA0 => 0,                          // unused
             (0xFF, _) => self.read_io(offset),                         // io registers
             _ => 0xFF,
+        };
+
+        if self.debugger.has_watchpoints() {
+            self.debugger.on_read(addr, result);
         }
+
+        result
     }
 
     fn write_not_ticked(&mut self, addr: u16, data: u8, block_for_dma: Option<BusType>) {
         let page = (addr >> 8) as u8;
         let offset = addr as u8;
 
+        if self.debugger.has_watchpoints() {
+            self.debugger.on_write(addr, data);
+        }
+
         match (page, block_for_dma) {
             (0x00..=0x7F, Some(BusType::External)) => {} // ignore writes
             (0x00..=0x7F, _) => self.cartridge.write_to_bank_controller(addr, data), // cart
@@ -694,10 +936,30 @@ impl CpuBusProvider for Bus {
     }
 
     fn read_no_oam_bug(&mut self, addr: u16) -> u8 {
+        if addr <= 0x7FFF {
+            self.cartridge.on_cpu_read(addr);
+        }
+
         let result = self.read_not_ticked(addr, self.oam_dma.conflicting_bus());
         self.on_cpu_machine_cycle();
         result
     }
+
+    fn is_tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    fn trace_instruction(&mut self, registers: CpuRegisters) {
+        let mut pcmem = [0; 4];
+        for (i, byte) in pcmem.iter_mut().enumerate() {
+            *byte = self.debug_read(registers.pc.wrapping_add(i as u16));
+        }
+        let ly = self.ppu.read_ly();
+
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.trace(registers, pcmem, ly);
+        }
+    }
 }
 
 /// This is an implementation of Savable of the attributes that cannot implement
@@ -717,8 +979,7 @@ impl Savable for Bus {
     fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
         let stopped_u8 = reader.read_u8()?;
         if stopped_u8 > 1 {
-            // TODO: maybe should not panic?
-            panic!("invalid boolean value");
+            return Err(SaveError::InvalidValue("stopped"));
         }
         self.stopped = stopped_u8 == 1;
 
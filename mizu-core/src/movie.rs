@@ -0,0 +1,204 @@
+use std::convert::TryInto;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use serde::{Deserialize, Serialize};
+
+use crate::joypad::JoypadButton;
+use crate::save_state::SaveError;
+
+/// The first bytes of every movie file, used to identify the file
+const MOVIE_MAGIC: &[u8; 8] = b"MIZUMOVI";
+
+/// Must be incremented when the movie format changes
+const MOVIE_VERSION: u16 = 1;
+
+/// The point from which the movie starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MovieAnchor {
+    /// The recording started before the first frame was emulated
+    PowerOn,
+    /// The recording started in the middle of the emulation
+    SaveState,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct MovieEvent {
+    /// The number of clocks since the start of the movie, this is the same
+    /// clock used for PPU cycles, so it is not affected by CGB double speed
+    clock: u64,
+    button: JoypadButton,
+    pressed: bool,
+}
+
+/// A recording of the joypad inputs of a run, which can be played back with
+/// [`GameBoy::play_movie`](crate::GameBoy::play_movie) to reproduce the run
+/// exactly.
+///
+/// The movie contains a save state of the point where it started. This also
+/// pins the wall-clock time used by the cartridge RTC, as the RTC only reads
+/// the system time when the cartridge is loaded, after that it is clocked by
+/// the emulation.
+#[derive(Serialize, Deserialize)]
+pub struct Movie {
+    game_title: String,
+    anchor: MovieAnchor,
+    start_state: Vec<u8>,
+    events: Vec<MovieEvent>,
+}
+
+impl Movie {
+    pub fn game_title(&self) -> &str {
+        &self.game_title
+    }
+
+    pub fn anchor(&self) -> MovieAnchor {
+        self.anchor
+    }
+
+    pub(crate) fn start_state(&self) -> &[u8] {
+        &self.start_state
+    }
+
+    /// Serializes the movie to be stored in a file
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SaveError> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MOVIE_MAGIC);
+        data.extend_from_slice(&MOVIE_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut data, self)?;
+
+        Ok(data)
+    }
+
+    /// Parses a movie created by [`to_bytes`](Self::to_bytes)
+    pub fn from_bytes(data: &[u8]) -> Result<Self, SaveError> {
+        let header_len = MOVIE_MAGIC.len() + 2;
+
+        if data.len() < header_len || &data[..MOVIE_MAGIC.len()] != MOVIE_MAGIC {
+            return Err(SaveError::InvalidMovieHeader);
+        }
+
+        let version = u16::from_le_bytes(data[MOVIE_MAGIC.len()..header_len].try_into().unwrap());
+        if version != MOVIE_VERSION {
+            return Err(SaveError::UnsupportedMovieVersion(version));
+        }
+
+        Ok(bincode::deserialize(&data[header_len..])?)
+    }
+}
+
+pub struct MovieRecorder {
+    start_clock: u64,
+    movie: Movie,
+}
+
+impl MovieRecorder {
+    pub fn new(
+        start_clock: u64,
+        game_title: String,
+        anchor: MovieAnchor,
+        start_state: Vec<u8>,
+    ) -> Self {
+        Self {
+            start_clock,
+            movie: Movie {
+                game_title,
+                anchor,
+                start_state,
+                events: Vec::new(),
+            },
+        }
+    }
+
+    pub fn record(&mut self, clock: u64, button: JoypadButton, pressed: bool) {
+        self.movie.events.push(MovieEvent {
+            clock: clock - self.start_clock,
+            button,
+            pressed,
+        });
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+pub struct MoviePlayer {
+    start_clock: u64,
+    events: Peekable<IntoIter<MovieEvent>>,
+}
+
+impl MoviePlayer {
+    pub fn new(start_clock: u64, movie: &Movie) -> Self {
+        Self {
+            start_clock,
+            events: movie.events.clone().into_iter().peekable(),
+        }
+    }
+
+    /// Returns the next event if it should be applied at `clock`, must be
+    /// called until it returns `None` before executing the next instruction
+    pub fn next_event(&mut self, clock: u64) -> Option<(JoypadButton, bool)> {
+        let clock = clock - self.start_clock;
+
+        if self.events.peek()?.clock <= clock {
+            self.events
+                .next()
+                .map(|event| (event.button, event.pressed))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_finished(&mut self) -> bool {
+        self.events.peek().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut recorder = MovieRecorder::new(
+            1000,
+            String::from("GAME"),
+            MovieAnchor::SaveState,
+            vec![1, 2, 3],
+        );
+        recorder.record(1000, JoypadButton::Start, true);
+        recorder.record(1500, JoypadButton::Start, false);
+
+        let data = recorder.finish().to_bytes().unwrap();
+        let movie = Movie::from_bytes(&data).unwrap();
+        assert_eq!(movie.game_title(), "GAME");
+        assert_eq!(movie.anchor(), MovieAnchor::SaveState);
+        assert_eq!(movie.start_state(), &[1, 2, 3]);
+
+        // the events are relative to the start of the movie
+        let mut player = MoviePlayer::new(5000, &movie);
+        assert_eq!(player.next_event(5000), Some((JoypadButton::Start, true)));
+        assert_eq!(player.next_event(5000), None);
+        assert_eq!(player.next_event(5499), None);
+        assert_eq!(player.next_event(5500), Some((JoypadButton::Start, false)));
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    fn invalid_header() {
+        let movie = MovieRecorder::new(0, String::new(), MovieAnchor::PowerOn, Vec::new()).finish();
+        let mut data = movie.to_bytes().unwrap();
+
+        assert!(matches!(
+            Movie::from_bytes(&data[..4]),
+            Err(SaveError::InvalidMovieHeader)
+        ));
+
+        data[MOVIE_MAGIC.len()] = 0xFF;
+        assert!(matches!(
+            Movie::from_bytes(&data),
+            Err(SaveError::UnsupportedMovieVersion(0xFF))
+        ));
+    }
+}
@@ -6,13 +6,17 @@ mod lcd;
 mod sprite;
 
 use crate::memory::{InterruptManager, InterruptType};
+use crate::save_state::{Savable, SaveError};
 use crate::GameboyConfig;
 use bg_attribs::BgAttribute;
 use bitflags::bitflags;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use colors::{Color, ColorPalette, ColorPalettesCollection};
 use fifo::{BgFifo, SpriteFifo, SpritePriorityMode};
 use lcd::Lcd;
+use serde::{Deserialize, Serialize};
 use sprite::{SelectedSprite, Sprite};
+use std::io::{Read, Write};
 
 bitflags! {
     struct LcdControl: u8 {
@@ -127,7 +131,7 @@ impl LcdStatus {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 struct Fetcher {
     delay_counter: u8,
     data: Option<([u8; 8], BgAttribute)>,
@@ -363,11 +367,7 @@ impl Ppu {
     }
 
     pub fn write_vram(&mut self, addr: u16, data: u8) {
-        // here since this is the only place vram is written to, no need
-        // to make another function `write_vram_banked`
-        let offset = addr as usize & 0x1FFF;
-        let bank_start = self.vram_bank as usize * 0x2000;
-        self.vram[bank_start + offset] = data;
+        self.write_vram_banked(self.vram_bank, addr, data);
     }
 
     pub fn read_oam(&self, addr: u16) -> u8 {
@@ -811,7 +811,7 @@ impl Ppu {
                 || (self.mode_3_end_cycle != 0 && self.mode_3_end_cycle + 8 > self.cycle))
     }
 
-    fn read_oam_no_lock(&self, addr: u16) -> u8 {
+    pub fn read_oam_no_lock(&self, addr: u16) -> u8 {
         let addr = addr & 0xFF;
         self.oam[addr as usize / 4].get_at_offset(addr as u8 % 4)
     }
@@ -853,12 +853,20 @@ impl Ppu {
         }
     }
 
-    fn read_vram_banked(&self, bank: u8, addr: u16) -> u8 {
+    pub fn read_vram_banked(&self, bank: u8, addr: u16) -> u8 {
         let offset = addr as usize & 0x1FFF;
         let bank_start = bank as usize * 0x2000;
         self.vram[bank_start + offset]
     }
 
+    /// Writes to any bank, the CPU can only write to the current bank through
+    /// [`write_vram`](Self::write_vram), this is used for debugging
+    pub fn write_vram_banked(&mut self, bank: u8, addr: u16, data: u8) {
+        let offset = addr as usize & 0x1FFF;
+        let bank_start = bank as usize * 0x2000;
+        self.vram[bank_start + offset] = data;
+    }
+
     /// return true, if this is the last draw in the current scanline, and
     /// mode 0 is being activated
     fn draw(&mut self) -> bool {
@@ -1146,3 +1154,114 @@ impl Ppu {
         self.window_y_counter = 0;
     }
 }
+
+/// `vram` and `oam` are too large to be serialized with serde, so the
+/// registers are serialized one by one, and the memory is stored raw.
+///
+/// The fetcher and the FIFOs are included so that a state saved in the middle
+/// of a scanline (mode 3) continues drawing exactly where it stopped.
+impl Savable for Ppu {
+    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+        writer.write_u8(self.lcd_control.bits())?;
+        writer.write_u8(self.lcd_status.bits())?;
+        writer.write_u8(self.scroll_y)?;
+        writer.write_u8(self.scroll_x)?;
+        writer.write_u8(self.ly)?;
+        writer.write_u8(self.lyc)?;
+        writer.write_u8(self.stat_interrupt_line as u8)?;
+        writer.write_u8(self.dmg_bg_palette)?;
+        writer.write_all(&self.dmg_sprite_palettes)?;
+        writer.write_u8(self.windows_y)?;
+        writer.write_u8(self.windows_x)?;
+
+        writer.write_all(&self.vram)?;
+        writer.write_u8(self.vram_bank)?;
+        for sprite in self.oam.iter() {
+            for offset in 0..4 {
+                writer.write_u8(sprite.get_at_offset(offset))?;
+            }
+        }
+        bincode::serialize_into(&mut *writer, &self.selected_oam)?;
+        writer.write_u8(self.selected_oam_size)?;
+
+        bincode::serialize_into(&mut *writer, &self.cgb_bg_palettes)?;
+        bincode::serialize_into(&mut *writer, &self.cgb_sprite_palettes)?;
+
+        writer.write_u8(self.fine_scroll_x_discard)?;
+        bincode::serialize_into(&mut *writer, &self.fetcher)?;
+        writer.write_u8(self.is_drawing_window as u8)?;
+        writer.write_u8(self.window_y_counter)?;
+
+        self.bg_fifo.save(writer)?;
+        self.sprite_fifo.save(writer)?;
+        self.lcd.save(writer)?;
+
+        writer.write_u16::<LittleEndian>(self.cycle)?;
+        writer.write_u8(self.scanline)?;
+        writer.write_u16::<LittleEndian>(self.mode_3_end_cycle)?;
+        writer.write_u8(self.lcd_turned_on as u8)?;
+        bincode::serialize_into(&mut *writer, &self.sprite_priority_mode)?;
+        writer.write_u8(self.is_cgb_mode as u8)?;
+        bincode::serialize_into(&mut *writer, &self.config)?;
+
+        Ok(())
+    }
+
+    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+        self.lcd_control = LcdControl::from_bits_truncate(reader.read_u8()?);
+        self.lcd_status = LcdStatus::from_bits_truncate(reader.read_u8()?);
+        self.scroll_y = reader.read_u8()?;
+        self.scroll_x = reader.read_u8()?;
+        self.ly = reader.read_u8()?;
+        self.lyc = reader.read_u8()?;
+        self.stat_interrupt_line = reader.read_u8()? != 0;
+        self.dmg_bg_palette = reader.read_u8()?;
+        reader.read_exact(&mut self.dmg_sprite_palettes)?;
+        self.windows_y = reader.read_u8()?;
+        self.windows_x = reader.read_u8()?;
+
+        reader.read_exact(&mut self.vram)?;
+        self.vram_bank = reader.read_u8()?;
+        for sprite in self.oam.iter_mut() {
+            for offset in 0..4 {
+                sprite.set_at_offset(offset, reader.read_u8()?);
+            }
+        }
+        self.selected_oam = bincode::deserialize_from(&mut *reader)?;
+        self.selected_oam_size = reader.read_u8()?;
+
+        self.cgb_bg_palettes = bincode::deserialize_from(&mut *reader)?;
+        self.cgb_sprite_palettes = bincode::deserialize_from(&mut *reader)?;
+
+        self.fine_scroll_x_discard = reader.read_u8()?;
+        self.fetcher = bincode::deserialize_from(&mut *reader)?;
+        self.is_drawing_window = reader.read_u8()? != 0;
+        self.window_y_counter = reader.read_u8()?;
+
+        self.bg_fifo.load(reader)?;
+        self.sprite_fifo.load(reader)?;
+        self.lcd.load(reader)?;
+
+        self.cycle = reader.read_u16::<LittleEndian>()?;
+        self.scanline = reader.read_u8()?;
+        self.mode_3_end_cycle = reader.read_u16::<LittleEndian>()?;
+        self.lcd_turned_on = reader.read_u8()? != 0;
+        self.sprite_priority_mode = bincode::deserialize_from(&mut *reader)?;
+        self.is_cgb_mode = reader.read_u8()? != 0;
+        self.config = bincode::deserialize_from(&mut *reader)?;
+
+        Ok(())
+    }
+
+    fn object_size() -> u64 {
+        0x40000
+    }
+
+    fn current_save_size(&self) -> Result<u64, SaveError> {
+        let mut tmp_save = Vec::with_capacity(Self::object_size() as usize);
+
+        self.save(&mut tmp_save)?;
+
+        Ok(tmp_save.len() as u64)
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 macro_rules! color {
     ($r:expr, $g:expr, $b:expr) => {
         Color {
@@ -35,7 +37,7 @@ impl Color {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct ColorPalette {
     data: [u16; 4],
 }
@@ -87,6 +89,7 @@ impl ColorPalette {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ColorPalettesCollection {
     index: u8,
     auto_increment: bool,
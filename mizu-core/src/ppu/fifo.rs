@@ -1,15 +1,19 @@
 use super::colors::ColorPalette;
 use super::sprite::SelectedSprite;
+use crate::save_state::{Savable, SaveError};
+use byteorder::{ReadBytesExt, WriteBytesExt};
 use fixed_vec_deque::FixedVecDeque;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum SpritePriorityMode {
     ByIndex, // CGB
     ByCoord, // DMG
 }
 
 /// Background store the `bg_priority` of the `bg_attribs` for the pixel data
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
 pub struct BgFifoPixel {
     pub color: u8,
     pub palette: ColorPalette,
@@ -18,7 +22,7 @@ pub struct BgFifoPixel {
 
 /// Sprite store the index of the sprite, as in CGB priority is done by index
 ///  and not by coordinate
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
 pub struct SpriteFifoPixel {
     pub color: u8,
     pub palette: ColorPalette,
@@ -129,3 +133,75 @@ impl SpriteFifo {
         self.pixels.clear();
     }
 }
+
+/// `FixedVecDeque` does not implement serde traits, so the pixels are
+/// saved as a length followed by the pixels from front to back
+impl Savable for BgFifo {
+    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+        writer.write_u8(self.pixels.len() as u8)?;
+        for pixel in self.pixels.iter() {
+            bincode::serialize_into(&mut *writer, pixel)?;
+        }
+
+        Ok(())
+    }
+
+    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+        let len = reader.read_u8()?;
+
+        self.pixels.clear();
+        for _ in 0..len {
+            *self.pixels.push_back() = bincode::deserialize_from(&mut *reader)?;
+        }
+
+        Ok(())
+    }
+
+    fn object_size() -> u64 {
+        256
+    }
+
+    fn current_save_size(&self) -> Result<u64, SaveError> {
+        let mut tmp_save = Vec::with_capacity(Self::object_size() as usize);
+
+        self.save(&mut tmp_save)?;
+
+        Ok(tmp_save.len() as u64)
+    }
+}
+
+impl Savable for SpriteFifo {
+    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+        bincode::serialize_into(&mut *writer, &self.sprite_priority_mode)?;
+        writer.write_u8(self.pixels.len() as u8)?;
+        for pixel in self.pixels.iter() {
+            bincode::serialize_into(&mut *writer, pixel)?;
+        }
+
+        Ok(())
+    }
+
+    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+        self.sprite_priority_mode = bincode::deserialize_from(&mut *reader)?;
+        let len = reader.read_u8()?;
+
+        self.pixels.clear();
+        for _ in 0..len {
+            *self.pixels.push_back() = bincode::deserialize_from(&mut *reader)?;
+        }
+
+        Ok(())
+    }
+
+    fn object_size() -> u64 {
+        256
+    }
+
+    fn current_save_size(&self) -> Result<u64, SaveError> {
+        let mut tmp_save = Vec::with_capacity(Self::object_size() as usize);
+
+        self.save(&mut tmp_save)?;
+
+        Ok(tmp_save.len() as u64)
+    }
+}
@@ -1,4 +1,7 @@
 use super::colors::Color;
+use crate::save_state::{Savable, SaveError};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
 
 pub const LCD_WIDTH: usize = 160;
 pub const LCD_HEIGHT: usize = 144;
@@ -106,3 +109,36 @@ impl Lcd {
         self.selected_buffer ^ 1
     }
 }
+
+impl Savable for Lcd {
+    fn save<W: Write>(&self, writer: &mut W) -> Result<(), SaveError> {
+        writer.write_u8(self.x)?;
+        writer.write_u8(self.selected_buffer as u8)?;
+        for buf in self.buf.iter() {
+            writer.write_all(buf)?;
+        }
+        writer.write_all(&self.raw_buf)?;
+
+        Ok(())
+    }
+
+    fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), SaveError> {
+        self.x = reader.read_u8()?;
+        self.selected_buffer = reader.read_u8()? as usize & 1;
+        for buf in self.buf.iter_mut() {
+            reader.read_exact(buf)?;
+        }
+        reader.read_exact(&mut self.raw_buf)?;
+
+        Ok(())
+    }
+
+    fn object_size() -> u64 {
+        0x33000
+    }
+
+    fn current_save_size(&self) -> Result<u64, SaveError> {
+        // + 2 for `x` and `selected_buffer`
+        Ok((self.buf.len() + 1) as u64 * self.raw_buf.len() as u64 + 2)
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+
+/// Runs of equal bytes shorter than this are stored as part of the
+/// different bytes, as storing them as a new chunk takes more space
+const MIN_EQUAL_RUN: usize = 8;
+
+/// Encodes `target` as the difference from `base`.
+///
+/// The result starts with the length of `target` (u32), then a list of chunks.
+/// Each chunk is the number of bytes equal to `base` (u32), the number of
+/// different bytes (u32), followed by the different bytes.
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let equal_at = |i: usize| base.get(i) == Some(&target[i]);
+
+    let mut result = Vec::new();
+    result.extend_from_slice(&(target.len() as u32).to_le_bytes());
+
+    let mut i = 0;
+    while i < target.len() {
+        let equal_start = i;
+        while i < target.len() && equal_at(i) {
+            i += 1;
+        }
+        let equal_len = i - equal_start;
+
+        let different_start = i;
+        while i < target.len() {
+            if equal_at(i) {
+                let equal_run = (i..target.len().min(i + MIN_EQUAL_RUN))
+                    .take_while(|&j| equal_at(j))
+                    .count();
+
+                if equal_run == MIN_EQUAL_RUN {
+                    break;
+                }
+                i += equal_run;
+            } else {
+                i += 1;
+            }
+        }
+        let different = &target[different_start..i];
+
+        result.extend_from_slice(&(equal_len as u32).to_le_bytes());
+        result.extend_from_slice(&(different.len() as u32).to_le_bytes());
+        result.extend_from_slice(different);
+    }
+
+    result
+}
+
+/// Reconstructs the `target` from the `base` and the result of `encode_delta`
+fn decode_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let read_u32 =
+        |pos: usize| u32::from_le_bytes(delta[pos..pos + 4].try_into().unwrap()) as usize;
+
+    let mut result = Vec::with_capacity(read_u32(0));
+
+    let mut pos = 4;
+    while pos < delta.len() {
+        let equal_len = read_u32(pos);
+        let different_len = read_u32(pos + 4);
+        pos += 8;
+
+        let start = result.len();
+        result.extend_from_slice(&base[start..start + equal_len]);
+        result.extend_from_slice(&delta[pos..pos + different_len]);
+        pos += different_len;
+    }
+
+    result
+}
+
+/// Keeps a history of save states, one every `interval` frames.
+///
+/// Only the most recent save state is stored in full, each older save state
+/// is stored as the difference from the one after it. This way the oldest
+/// save state can be dropped when the memory budget is exceeded without
+/// needing to decode anything.
+pub struct RewindBuffer {
+    interval: u32,
+    memory_budget: usize,
+    /// number of frames since the latest save state
+    frames_counter: u32,
+
+    latest: Vec<u8>,
+    deltas: VecDeque<Vec<u8>>,
+    deltas_size: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(interval: u32, memory_budget: usize, initial_state: Vec<u8>) -> Self {
+        Self {
+            interval: interval.max(1),
+            memory_budget,
+            frames_counter: 0,
+            latest: initial_state,
+            deltas: VecDeque::new(),
+            deltas_size: 0,
+        }
+    }
+
+    /// Called after every frame, returns `true` if a new save state should be
+    /// pushed with [`push`](Self::push)
+    pub fn frame_finished(&mut self) -> bool {
+        self.frames_counter += 1;
+        self.frames_counter >= self.interval
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        let old_latest = std::mem::replace(&mut self.latest, state);
+        let delta = encode_delta(&self.latest, &old_latest);
+
+        self.deltas_size += delta.len();
+        self.deltas.push_back(delta);
+        self.frames_counter = 0;
+
+        while self.latest.len() + self.deltas_size > self.memory_budget {
+            match self.deltas.pop_front() {
+                Some(oldest) => self.deltas_size -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Removes the save states newer than the target and returns the target
+    /// save state along with the number of frames to get back to it.
+    ///
+    /// The target is the newest save state which is at least `frames` frames
+    /// old, or the oldest one if there is no such save state. The returned
+    /// save state is kept in the buffer so it can be rewound to again.
+    pub fn rewind(&mut self, frames: u32) -> (&[u8], u32) {
+        let steps = if self.frames_counter >= frames {
+            0
+        } else {
+            let remaining = frames - self.frames_counter;
+            remaining.div_ceil(self.interval) as usize
+        }
+        .min(self.deltas.len());
+
+        for _ in 0..steps {
+            let delta = self.deltas.pop_back().unwrap();
+            self.deltas_size -= delta.len();
+            self.latest = decode_delta(&self.latest, &delta);
+        }
+
+        let rewound_frames = self.frames_counter + steps as u32 * self.interval;
+        self.frames_counter = 0;
+
+        (&self.latest, rewound_frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_round_trip() {
+        let base: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let mut target = base.clone();
+        // a short run of equal bytes inside the different bytes
+        target[10] = 0xFF;
+        target[13] = 0xFF;
+        target[100..120].fill(0);
+        // longer than the base
+        target.extend_from_slice(&[1, 2, 3]);
+
+        let delta = encode_delta(&base, &target);
+        assert!(delta.len() < target.len());
+        assert_eq!(decode_delta(&base, &delta), target);
+
+        // shorter than the base
+        let target = &base[..50];
+        assert_eq!(decode_delta(&base, &encode_delta(&base, target)), target);
+
+        // equal to the base, only the length and one chunk
+        let delta = encode_delta(&base, &base);
+        assert_eq!(delta.len(), 12);
+        assert_eq!(decode_delta(&base, &delta), base);
+    }
+
+    #[test]
+    fn rewind_to_older_states() {
+        let mut buffer = RewindBuffer::new(2, usize::MAX, vec![0; 16]);
+
+        for i in 1..=3 {
+            assert!(!buffer.frame_finished());
+            assert!(buffer.frame_finished());
+            buffer.push(vec![i; 16]);
+        }
+
+        // one frame after the last state
+        buffer.frame_finished();
+
+        let (state, frames) = buffer.rewind(4);
+        assert_eq!(state, &[1; 16][..]);
+        assert_eq!(frames, 5);
+
+        // further than the oldest state
+        let (state, frames) = buffer.rewind(100);
+        assert_eq!(state, &[0; 16][..]);
+        assert_eq!(frames, 2);
+    }
+}
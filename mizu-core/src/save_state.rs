@@ -1,7 +1,15 @@
 use bincode::Error as bincodeError;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::convert::From;
 use std::io::{Cursor, Error as ioError, Read, Write};
 
+/// The first bytes of every save state, used to identify the file
+const SAVE_STATE_MAGIC: &[u8; 8] = b"MIZUSAVE";
+
+/// Must be incremented when a change to any `Savable` object, or the sections
+/// included in a save state, makes older save states incompatible
+pub const SAVE_STATE_VERSION: u16 = 2;
+
 macro_rules! impl_savable {
     ($struct_name: ident, $object_size: expr) => {
         impl crate::save_state::Savable for $struct_name {
@@ -82,6 +90,144 @@ pub fn load_object<T: Savable>(object: &mut T, data: &[u8]) -> Result<(), SaveEr
     Ok(())
 }
 
+/// Tag used to identify a section in the save state, 4 ASCII characters
+pub type SectionTag = [u8; 4];
+
+/// Builds a save state from multiple `Savable` objects.
+///
+/// The save state is self describing, it is composed of a header
+/// (`SAVE_STATE_MAGIC` followed by `SAVE_STATE_VERSION`), then a list of sections.
+/// Each section is a `SectionTag`, then the length of the data as `u32`, then
+/// the object data as produced by `save_object` (or the raw data for
+/// sections added with [`add_raw`](Self::add_raw)).
+pub struct SaveStateBuilder {
+    data: Vec<u8>,
+}
+
+impl Default for SaveStateBuilder {
+    fn default() -> Self {
+        let mut data = Vec::new();
+        data.extend_from_slice(SAVE_STATE_MAGIC);
+        data.extend_from_slice(&SAVE_STATE_VERSION.to_le_bytes());
+
+        Self { data }
+    }
+}
+
+impl SaveStateBuilder {
+    pub fn add<T: Savable>(&mut self, tag: SectionTag, object: &T) -> Result<(), SaveError> {
+        let object_data = save_object(object)?;
+
+        self.data.write_all(&tag)?;
+        self.data
+            .write_u32::<LittleEndian>(object_data.len() as u32)?;
+        self.data.write_all(&object_data)?;
+
+        Ok(())
+    }
+
+    /// Adds `data` as is without padding, for large memories that only some
+    /// cartridges have, so that they do not increase the size of all save
+    /// states
+    pub fn add_raw(&mut self, tag: SectionTag, data: &[u8]) -> Result<(), SaveError> {
+        self.data.write_all(&tag)?;
+        self.data.write_u32::<LittleEndian>(data.len() as u32)?;
+        self.data.write_all(data)?;
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Parses a save state produced by `SaveStateBuilder`, the header and the
+/// boundaries of all sections are validated when created, so that errors
+/// are found before any object is modified.
+pub struct SaveStateReader<'a> {
+    sections: Vec<(SectionTag, &'a [u8])>,
+}
+
+impl<'a> SaveStateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, SaveError> {
+        let header_len = SAVE_STATE_MAGIC.len() + 2;
+
+        if data.len() < header_len || &data[..SAVE_STATE_MAGIC.len()] != SAVE_STATE_MAGIC {
+            return Err(SaveError::InvalidSaveStateHeader);
+        }
+
+        let mut cursor = Cursor::new(&data[SAVE_STATE_MAGIC.len()..]);
+        let version = cursor.read_u16::<LittleEndian>()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(SaveError::UnsupportedSaveStateVersion(version));
+        }
+
+        let mut sections = Vec::new();
+        let mut rest = &data[header_len..];
+
+        while !rest.is_empty() {
+            if rest.len() < 8 {
+                return Err(SaveError::InvalidSaveStateHeader);
+            }
+
+            let mut tag = [0; 4];
+            tag.copy_from_slice(&rest[..4]);
+            let len = Cursor::new(&rest[4..8]).read_u32::<LittleEndian>()? as usize;
+            rest = &rest[8..];
+
+            if rest.len() < len {
+                return Err(SaveError::InvalidSaveStateHeader);
+            }
+
+            sections.push((tag, &rest[..len]));
+            rest = &rest[len..];
+        }
+
+        Ok(Self { sections })
+    }
+
+    /// Checks that a section with the `tag` exists, and its size matches
+    /// `T::object_size`
+    pub fn check<T: Savable>(&self, tag: SectionTag) -> Result<(), SaveError> {
+        if self.section(tag)?.len() as u64 != T::object_size() {
+            Err(SaveError::LoadSizeDoesNotMatch)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn load<T: Savable>(&self, tag: SectionTag, object: &mut T) -> Result<(), SaveError> {
+        load_object(object, self.section(tag)?)
+    }
+
+    /// Checks that a section added with [`SaveStateBuilder::add_raw`] exists,
+    /// and its size is `len`
+    pub fn check_raw(&self, tag: SectionTag, len: usize) -> Result<(), SaveError> {
+        if self.section(tag)?.len() != len {
+            Err(SaveError::LoadSizeDoesNotMatch)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Loads a section added with [`SaveStateBuilder::add_raw`] into `data`
+    pub fn load_raw(&self, tag: SectionTag, data: &mut [u8]) -> Result<(), SaveError> {
+        self.check_raw(tag, data.len())?;
+        data.copy_from_slice(self.section(tag)?);
+
+        Ok(())
+    }
+
+    fn section(&self, tag: SectionTag) -> Result<&'a [u8], SaveError> {
+        self.sections
+            .iter()
+            .find(|(section_tag, _)| section_tag == &tag)
+            .map(|(_, data)| *data)
+            .ok_or_else(|| SaveError::MissingSection(String::from_utf8_lossy(&tag).into_owned()))
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum SaveError {
     #[error("Io Eror: {0}")]
@@ -92,6 +238,20 @@ pub enum SaveError {
     SaveSizeExceedLimit,
     #[error("Load size of the input data does not match `object_size`")]
     LoadSizeDoesNotMatch,
+    #[error("The data is not a valid save state")]
+    InvalidSaveStateHeader,
+    #[error("The save state version {0} is not supported")]
+    UnsupportedSaveStateVersion(u16),
+    #[error("The save state does not contain the section {0:?}")]
+    MissingSection(String),
+    #[error("The save state contains an invalid value for {0}")]
+    InvalidValue(&'static str),
+    #[error("The save state was created for another game {0:?}")]
+    GameMismatch(String),
+    #[error("The data is not a valid movie")]
+    InvalidMovieHeader,
+    #[error("The movie version {0} is not supported")]
+    UnsupportedMovieVersion(u16),
 }
 
 impl From<ioError> for SaveError {
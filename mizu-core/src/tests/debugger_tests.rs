@@ -0,0 +1,197 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::cartridge::Cartridge;
+use crate::{
+    BreakReason, GameBoy, GameboyConfig, HeaderValidation, Labels, MemoryRegion, TraceEntry,
+    TraceSink, WatchKind,
+};
+
+fn new_gameboy() -> GameBoy {
+    GameBoy::new(
+        "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb",
+        None,
+        GameboyConfig {
+            is_dmg: false,
+            ..GameboyConfig::default()
+        },
+    )
+    .unwrap()
+}
+
+#[test]
+fn breakpoint_stops_before_instruction() {
+    let mut gb = new_gameboy();
+
+    for _ in 0..5 {
+        assert_eq!(gb.clock_for_frame(), None);
+    }
+
+    let pc = gb.cpu_registers().pc;
+    gb.debugger().add_breakpoint(pc);
+
+    assert_eq!(gb.clock_for_frame(), Some(BreakReason::Breakpoint(pc)));
+    assert_eq!(gb.cpu_registers().pc, pc);
+
+    // stepping executes the instruction even if it has a breakpoint
+    assert_eq!(gb.step(), None);
+    assert_ne!(gb.cpu_registers().pc, pc);
+
+    gb.debugger().clear();
+    assert_eq!(gb.clock_for_frame(), None);
+}
+
+#[test]
+fn step_without_breakpoints_does_not_skip_later_breakpoint() {
+    let mut gb = new_gameboy();
+
+    for _ in 0..5 {
+        gb.clock_for_frame();
+    }
+
+    assert_eq!(gb.step(), None);
+
+    let pc = gb.cpu_registers().pc;
+    gb.debugger().add_breakpoint(pc);
+
+    assert_eq!(gb.clock_for_frame(), Some(BreakReason::Breakpoint(pc)));
+    assert_eq!(gb.cpu_registers().pc, pc);
+}
+
+#[test]
+fn write_watchpoint_on_stack() {
+    let mut gb = new_gameboy();
+
+    for _ in 0..5 {
+        gb.clock_for_frame();
+    }
+
+    // the test rom calls functions all the time, which pushes to the stack
+    let addr = gb.cpu_registers().sp.wrapping_sub(1);
+    gb.debugger().add_watchpoint(addr, WatchKind::Write);
+
+    match gb.clock_for_frame() {
+        Some(BreakReason::WriteWatchpoint { addr: hit_addr, .. }) => assert_eq!(hit_addr, addr),
+        other => panic!("expected a write watchpoint, got {:?}", other),
+    }
+}
+
+#[test]
+fn run_to_scanline() {
+    let mut gb = new_gameboy();
+
+    for _ in 0..5 {
+        gb.clock_for_frame();
+    }
+
+    for &line in &[100, 0, 144] {
+        assert_eq!(gb.run_to_scanline(line), None);
+        assert_eq!(gb.bus.current_scanline(), line);
+    }
+}
+
+#[test]
+fn trace_in_gameboy_doctor_format() {
+    struct CollectSink(Rc<RefCell<Vec<TraceEntry>>>);
+
+    impl TraceSink for CollectSink {
+        fn trace(&mut self, entry: &TraceEntry) {
+            self.0.borrow_mut().push(*entry);
+        }
+    }
+
+    let mut gb = GameBoy::new(
+        "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb",
+        None,
+        GameboyConfig {
+            is_dmg: true,
+            ..GameboyConfig::default()
+        },
+    )
+    .unwrap();
+
+    let entries = Rc::new(RefCell::new(Vec::new()));
+    gb.set_trace_sink(Some(Box::new(CollectSink(entries.clone()))));
+
+    gb.step();
+    gb.step();
+
+    assert!(gb.set_trace_sink(None).is_some());
+    gb.step();
+
+    let entries = entries.borrow();
+    assert_eq!(entries.len(), 2);
+
+    // the first instruction in the rom is `nop`
+    assert!(entries[0]
+        .to_string()
+        .starts_with("A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,"));
+    assert_eq!(entries[0].cycles, 0);
+    assert_eq!(entries[1].registers.pc, 0x0101);
+    assert_eq!(entries[1].cycles, 4);
+}
+
+#[test]
+fn peek_poke_and_banked_access() {
+    let mut gb = new_gameboy();
+
+    for _ in 0..5 {
+        gb.clock_for_frame();
+    }
+
+    // the entry point is the same in ROM bank 0 and the memory map
+    assert_eq!(
+        gb.peek(0x0100),
+        gb.peek_banked(MemoryRegion::Rom, 0, 0x100).unwrap()
+    );
+
+    gb.poke(0xC010, 0x42);
+    assert_eq!(gb.peek(0xC010), 0x42);
+    assert_eq!(gb.peek_banked(MemoryRegion::Wram, 0, 0x10), Some(0x42));
+
+    // all the WRAM banks are accessible in CGB mode, even if not mapped
+    assert_eq!(gb.bank_count(MemoryRegion::Wram), 8);
+    assert!(gb.poke_banked(MemoryRegion::Wram, 7, 0x20, 0x99));
+    assert_eq!(gb.peek_banked(MemoryRegion::Wram, 7, 0x20), Some(0x99));
+
+    gb.poke(0xFF80, 0x12);
+    assert_eq!(gb.peek_banked(MemoryRegion::Hram, 0, 0), Some(0x12));
+
+    assert_eq!(
+        gb.peek_banked(MemoryRegion::Io, 0, 0x44),
+        Some(gb.peek(0xFF44))
+    );
+
+    assert_eq!(gb.peek_banked(MemoryRegion::Wram, 8, 0), None);
+    assert_eq!(gb.peek_banked(MemoryRegion::Oam, 0, 0xA0), None);
+    assert!(!gb.poke_banked(MemoryRegion::Vram, 2, 0, 0));
+}
+
+#[test]
+fn current_rom_bank_follows_mapper() {
+    // an MBC1 cartridge with 4 banks
+    let mut rom = vec![0; 0x10000];
+    rom[0x147] = 0x01;
+    rom[0x148] = 0x01;
+
+    let mut cartridge = Cartridge::from_bytes(rom, HeaderValidation::Ignore).unwrap();
+    assert_eq!(cartridge.current_rom_bank(), Some(1));
+
+    cartridge.write_to_bank_controller(0x2000, 2);
+    assert_eq!(cartridge.current_rom_bank(), Some(2));
+}
+
+#[test]
+fn disassemble_uses_labels_of_mapped_bank() {
+    let mut gb = new_gameboy();
+    gb.clock_for_frame();
+
+    let mut labels = Labels::default();
+    for bank in 1..4 {
+        labels.insert(bank, 0x4000, &format!("Bank{}", bank));
+    }
+    let bank = gb.bus.cartridge().current_rom_bank().unwrap();
+
+    let result = gb.disassemble(0x4000, 1, Some(&labels));
+    assert_eq!(result[0].label, Some(format!("Bank{}", bank)));
+}
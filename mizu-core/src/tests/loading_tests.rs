@@ -0,0 +1,245 @@
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::cartridge::Cartridge;
+use crate::{
+    CartridgeError, CartridgeInfo, CartridgeWarning, CgbSupport, GameBoy, GameboyConfig,
+    HeaderValidation, MapperType, MemorySramStorage,
+};
+
+const ROM_PATH: &str = "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb";
+
+fn screen_after_frames(gb: &mut GameBoy, frames: usize) -> u64 {
+    for _ in 0..frames {
+        gb.clock_for_frame();
+    }
+
+    crc::crc64::checksum_ecma(gb.screen_buffer())
+}
+
+#[test]
+fn from_bytes_matches_from_file() {
+    let config = GameboyConfig {
+        is_dmg: false,
+        ..GameboyConfig::default()
+    };
+    let rom = std::fs::read(ROM_PATH).unwrap();
+
+    let mut from_file = GameBoy::new(ROM_PATH, None, config).unwrap();
+    let mut from_bytes = GameBoy::from_bytes(&rom, None, config).unwrap();
+
+    assert_eq!(from_file.game_title(), from_bytes.game_title());
+    assert_eq!(
+        screen_after_frames(&mut from_file, 60),
+        screen_after_frames(&mut from_bytes, 60)
+    );
+}
+
+#[test]
+fn from_bytes_gzip() {
+    let config = GameboyConfig {
+        is_dmg: false,
+        ..GameboyConfig::default()
+    };
+    let rom = std::fs::read(ROM_PATH).unwrap();
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&rom).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut plain = GameBoy::from_bytes(&rom, None, config).unwrap();
+    let mut gzip = GameBoy::from_bytes(&compressed, None, config).unwrap();
+
+    assert_eq!(
+        screen_after_frames(&mut plain, 60),
+        screen_after_frames(&mut gzip, 60)
+    );
+}
+
+#[test]
+fn from_bytes_invalid_boot_rom_size() {
+    let rom = std::fs::read(ROM_PATH).unwrap();
+    let boot_rom = [0; 0x100];
+
+    let result = GameBoy::from_bytes(
+        &rom,
+        Some(&boot_rom),
+        GameboyConfig {
+            is_dmg: false,
+            ..GameboyConfig::default()
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(CartridgeError::InvalidBootRomSize {
+            expected: 0x900,
+            got: 0x100
+        })
+    ));
+}
+
+#[test]
+fn new_invalid_boot_rom_size() {
+    let rom_len = std::fs::metadata(ROM_PATH).unwrap().len() as usize;
+
+    // the ROM is too large to be a boot rom
+    let result = GameBoy::new(
+        ROM_PATH,
+        Some(ROM_PATH),
+        GameboyConfig {
+            is_dmg: false,
+            ..GameboyConfig::default()
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(CartridgeError::InvalidBootRomSize { expected: 0x900, got })
+            if got == rom_len
+    ));
+}
+
+#[test]
+fn sram_storage_round_trip() {
+    // MBC1+RAM+BATTERY
+    let rom =
+        std::fs::read("../test_roms/mooneye-gb_hwtests/emulator-only/mbc1/ram_256kb.gb").unwrap();
+    let config = GameboyConfig {
+        is_dmg: false,
+        ..GameboyConfig::default()
+    };
+    let storage = MemorySramStorage::default();
+
+    let mut gb = GameBoy::from_bytes(&rom, None, config).unwrap();
+    assert!(gb.has_battery());
+    gb.set_sram_storage(Some(Box::new(storage.clone())))
+        .unwrap();
+    assert_eq!(storage.data(), None);
+
+    for _ in 0..60 {
+        gb.clock_for_frame();
+    }
+    gb.flush_sram().unwrap();
+
+    let saved = storage.data().unwrap();
+    assert_eq!(Some(saved.clone()), gb.sram_data());
+
+    // the saved data is loaded when the storage is set
+    let mut gb = GameBoy::from_bytes(&rom, None, config).unwrap();
+    gb.set_sram_storage(Some(Box::new(MemorySramStorage::with_data(saved.clone()))))
+        .unwrap();
+    assert_eq!(gb.sram_data(), Some(saved));
+}
+
+#[test]
+fn sram_storage_not_used_if_load_fails() {
+    let rom =
+        std::fs::read("../test_roms/mooneye-gb_hwtests/emulator-only/mbc1/ram_256kb.gb").unwrap();
+    // too small for the 32KB SRAM
+    let storage = MemorySramStorage::with_data(vec![0x55; 0x100]);
+
+    let mut gb = GameBoy::from_bytes(&rom, None, GameboyConfig::default()).unwrap();
+    assert!(gb
+        .set_sram_storage(Some(Box::new(storage.clone())))
+        .is_err());
+
+    for _ in 0..60 {
+        gb.clock_for_frame();
+    }
+    gb.flush_sram().unwrap();
+    drop(gb);
+
+    assert_eq!(storage.data(), Some(vec![0x55; 0x100]));
+}
+
+#[test]
+fn header_validation_policy() {
+    let mut rom = std::fs::read(ROM_PATH).unwrap();
+    // break the header checksum
+    rom[0x14D] = rom[0x14D].wrapping_add(1);
+
+    let config = |header_validation| GameboyConfig {
+        is_dmg: false,
+        header_validation,
+    };
+
+    assert!(matches!(
+        GameBoy::from_bytes(&rom, None, config(HeaderValidation::Strict)),
+        Err(CartridgeError::InvalidChecksum { .. })
+    ));
+
+    let gb = GameBoy::from_bytes(&rom, None, config(HeaderValidation::Warn)).unwrap();
+    assert!(matches!(
+        gb.cartridge_warnings(),
+        [CartridgeWarning::InvalidChecksum { .. }]
+    ));
+
+    let gb = GameBoy::from_bytes(&rom, None, config(HeaderValidation::Ignore)).unwrap();
+    assert!(gb.cartridge_warnings().is_empty());
+}
+
+#[test]
+fn cartridge_info_from_bytes() {
+    let rom = std::fs::read(ROM_PATH).unwrap();
+    let info = CartridgeInfo::from_bytes(&rom).unwrap();
+
+    assert_eq!(info.title, "CPU_INSTRS");
+    assert_eq!(info.cgb_support, CgbSupport::Compatible);
+    assert_eq!(info.mapper, Some(MapperType::Mbc1 { multicart: false }));
+    assert_eq!(info.rom_size, rom.len());
+    assert!(info.nintendo_logo_valid);
+    assert!(info.header_checksum_valid);
+
+    let gb = GameBoy::from_bytes(&rom, None, GameboyConfig::default()).unwrap();
+    assert_eq!(gb.cartridge_info().title, info.title);
+
+    assert!(matches!(
+        CartridgeInfo::from_bytes(&rom[..0x100]),
+        Err(CartridgeError::MissingHeader)
+    ));
+}
+
+#[test]
+fn mmm01_uses_menu_header() {
+    let mut rom = std::fs::read(ROM_PATH).unwrap();
+
+    // put an MMM01 menu header in the last 32KB
+    let menu = rom.len() - 0x8000;
+    rom.copy_within(0x100..0x150, menu + 0x100);
+    rom[menu + 0x134..=menu + 0x142].copy_from_slice(b"MENU\0\0\0\0\0\0\0\0\0\0\0");
+    rom[menu + 0x147] = 0x0B;
+
+    let info = CartridgeInfo::from_bytes(&rom).unwrap();
+    assert_eq!(info.title, "MENU");
+    assert_eq!(info.mapper, Some(MapperType::Mmm01));
+
+    let config = GameboyConfig {
+        header_validation: HeaderValidation::Ignore,
+        ..GameboyConfig::default()
+    };
+    let gb = GameBoy::from_bytes(&rom, None, config).unwrap();
+    assert_eq!(gb.game_title(), "MENU");
+}
+
+#[test]
+fn mbc3_game_is_not_mbc3_clone() {
+    let rom = std::fs::read("../test_roms/rtc3test/rtc3test-1.gb").unwrap();
+
+    let info = CartridgeInfo::from_bytes(&rom).unwrap();
+    assert_eq!(info.mapper, Some(MapperType::Mbc3 { timer: true }));
+}
+
+#[test]
+fn padded_rom_warning_follows_policy() {
+    // 16KB, padded to 32KB
+    let rom = vec![0; 0x4000];
+
+    let cartridge = Cartridge::from_bytes(rom.clone(), HeaderValidation::Warn).unwrap();
+    assert!(cartridge
+        .warnings()
+        .contains(&CartridgeWarning::RomSizePadded(0x4000)));
+
+    let cartridge = Cartridge::from_bytes(rom, HeaderValidation::Ignore).unwrap();
+    assert!(cartridge.warnings().is_empty());
+}
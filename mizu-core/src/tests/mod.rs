@@ -62,9 +62,12 @@ macro_rules! gb_tests {
 // defined after the macro so that it can use it
 mod acid2_test;
 mod blargg_tests;
+mod debugger_tests;
+mod loading_tests;
 mod mooneye_tests;
 mod rtc3;
 mod samesuite_tests;
+mod save_state_tests;
 mod scribbltests;
 
 struct TestingGameBoy {
@@ -74,9 +77,12 @@ struct TestingGameBoy {
 
 impl TestingGameBoy {
     pub fn new<P: AsRef<Path>>(file_path: P, is_dmg: bool) -> Result<Self, CartridgeError> {
-        let cartridge = Cartridge::from_file(file_path)?;
+        let config = GameboyConfig {
+            is_dmg,
+            ..GameboyConfig::default()
+        };
 
-        let config = GameboyConfig { is_dmg };
+        let cartridge = Cartridge::from_file(file_path, config.header_validation)?;
 
         let is_cartridge_color = cartridge.is_cartridge_color();
         Ok(Self {
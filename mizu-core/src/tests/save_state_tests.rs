@@ -0,0 +1,216 @@
+use crate::cartridge::Cartridge;
+use crate::save_state::{save_object, Savable, SaveStateBuilder, SaveStateReader};
+use crate::{GameBoy, GameboyConfig, HeaderValidation};
+
+/// Run the emulator for some frames and collect the screen and audio output
+fn collect_frames(gb: &mut GameBoy, frames: usize) -> Vec<(u64, Vec<f32>)> {
+    (0..frames)
+        .map(|_| {
+            gb.clock_for_frame();
+            (
+                crc::crc64::checksum_ecma(gb.screen_buffer()),
+                gb.audio_buffer(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn save_state_resumes_identically() {
+    for &is_dmg in &[true, false] {
+        let mut gb = GameBoy::new(
+            "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb",
+            None,
+            GameboyConfig {
+                is_dmg,
+                ..GameboyConfig::default()
+            },
+        )
+        .unwrap();
+
+        for _ in 0..30 {
+            gb.clock_for_frame();
+        }
+
+        // stop in the middle of a frame
+        for _ in 0..1000 {
+            gb.cpu.next_instruction(&mut gb.bus);
+        }
+        // only compare audio produced after the save
+        gb.audio_buffer();
+
+        let state = gb.save_state().unwrap();
+        let expected = collect_frames(&mut gb, 60);
+
+        gb.load_state(&state).unwrap();
+        let result = collect_frames(&mut gb, 60);
+
+        assert!(expected == result, "emulation diverged after loading state");
+    }
+}
+
+#[test]
+fn save_state_keeps_frame_position() {
+    let mut gb = GameBoy::new(
+        "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb",
+        None,
+        GameboyConfig::default(),
+    )
+    .unwrap();
+
+    gb.clock_for_frame();
+    for _ in 0..1000 {
+        gb.clock_instruction().unwrap();
+    }
+    let frame_cycles = gb.frame_cycles;
+    assert_ne!(frame_cycles, 0);
+
+    let state = gb.save_state().unwrap();
+    gb.clock_for_frame();
+    assert_eq!(gb.frame_cycles, 0);
+
+    gb.load_state(&state).unwrap();
+    assert_eq!(gb.frame_cycles, frame_cycles);
+}
+
+#[test]
+fn raw_section_is_not_padded() {
+    let flash = [1, 2, 3, 4];
+
+    let mut builder = SaveStateBuilder::default();
+    builder.add_raw(*b"FLSH", &flash).unwrap();
+    let state = builder.finish();
+
+    let reader = SaveStateReader::new(&state).unwrap();
+    assert!(reader.check_raw(*b"FLSH", flash.len()).is_ok());
+    assert!(reader.check_raw(*b"FLSH", flash.len() + 1).is_err());
+
+    let mut result = [0; 4];
+    reader.load_raw(*b"FLSH", &mut result).unwrap();
+    assert_eq!(result, flash);
+}
+
+#[test]
+fn flash_only_in_mbc6_cartridges() {
+    for &(cartridge_type, has_flash) in &[(0x19, false), (0x20, true)] {
+        let mut rom = vec![0; 0x10000];
+        rom[0x147] = cartridge_type;
+        rom[0x148] = 0x01;
+
+        let cartridge = Cartridge::from_bytes(rom, HeaderValidation::Ignore).unwrap();
+        assert_eq!(cartridge.flash().is_some(), has_flash);
+        // the flash is saved in its own section, so the cartridge section
+        // keeps its size
+        assert_eq!(
+            save_object(&cartridge).unwrap().len() as u64,
+            Cartridge::object_size()
+        );
+    }
+}
+
+#[test]
+fn invalid_save_state_keeps_current_state() {
+    let mut gb = GameBoy::new(
+        "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb",
+        None,
+        GameboyConfig::default(),
+    )
+    .unwrap();
+
+    for _ in 0..30 {
+        gb.clock_for_frame();
+    }
+
+    let mut state = gb.save_state().unwrap();
+    // the first byte of the bus section is the `stopped` boolean, which is
+    // loaded after the CPU
+    let bus_section = state.windows(4).position(|tag| tag == b"BUS ").unwrap();
+    state[bus_section + 8] = 2;
+
+    for _ in 0..10 {
+        gb.clock_for_frame();
+    }
+    gb.audio_buffer();
+
+    let current_state = gb.save_state().unwrap();
+    let expected = collect_frames(&mut gb, 30);
+    gb.load_state(&current_state).unwrap();
+
+    assert!(gb.load_state(&state).is_err());
+    let result = collect_frames(&mut gb, 30);
+
+    assert!(
+        expected == result,
+        "failed load modified the emulation state"
+    );
+}
+
+#[test]
+fn rewind_resumes_identically() {
+    let mut gb = GameBoy::new(
+        "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb",
+        None,
+        GameboyConfig {
+            is_dmg: false,
+            ..GameboyConfig::default()
+        },
+    )
+    .unwrap();
+
+    for _ in 0..30 {
+        gb.clock_for_frame();
+    }
+    gb.audio_buffer();
+
+    gb.enable_rewind(2, 8 * 1024 * 1024).unwrap();
+
+    let expected = collect_frames(&mut gb, 40);
+
+    // 40 frames (20 states) back, to the point where rewind was enabled
+    assert_eq!(gb.rewind(40).unwrap(), 40);
+    let result = collect_frames(&mut gb, 40);
+
+    assert!(expected == result, "emulation diverged after rewind");
+
+    // not aligned to the interval, should go to the state before
+    assert_eq!(gb.rewind(3).unwrap(), 4);
+}
+
+#[test]
+fn movie_playback_reproduces_run() {
+    use crate::{JoypadButton, Movie, MovieAnchor};
+
+    let mut gb = GameBoy::new(
+        "../test_roms/blargg-gb-tests/cpu_instrs/cpu_instrs.gb",
+        None,
+        GameboyConfig {
+            is_dmg: false,
+            ..GameboyConfig::default()
+        },
+    )
+    .unwrap();
+
+    gb.start_movie_recording().unwrap();
+
+    let mut expected = Vec::new();
+    for i in 0..40 {
+        match i % 10 {
+            3 => gb.press_joypad(JoypadButton::A),
+            7 => gb.release_joypad(JoypadButton::A),
+            _ => {}
+        }
+        expected.extend(collect_frames(&mut gb, 1));
+    }
+
+    let movie = gb.stop_movie_recording().unwrap();
+    assert_eq!(movie.anchor(), MovieAnchor::PowerOn);
+
+    let movie = Movie::from_bytes(&movie.to_bytes().unwrap()).unwrap();
+    gb.play_movie(&movie).unwrap();
+    assert!(gb.is_playing_movie());
+
+    let result = collect_frames(&mut gb, 40);
+
+    assert!(expected == result, "emulation diverged in movie playback");
+    assert!(!gb.is_playing_movie());
+}
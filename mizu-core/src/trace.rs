@@ -0,0 +1,115 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Write;
+
+use crate::cpu::CpuRegisters;
+
+/// The state of the CPU before executing an instruction
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEntry {
+    pub registers: CpuRegisters,
+    /// The 4 bytes at `PC`, the first is the opcode
+    pub pcmem: [u8; 4],
+    /// The current scanline
+    pub ly: u8,
+    /// The number of clocks (4194304Hz) since tracing started
+    pub cycles: u64,
+}
+
+/// Formats the entry in the format used by [Gameboy Doctor], which is
+/// `A:00 F:11 B:22 C:33 D:44 E:55 H:66 L:77 SP:8888 PC:9999 PCMEM:AA,BB,CC,DD`.
+///
+/// [Gameboy Doctor]: https://github.com/robert/gameboy-doctor
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let r = &self.registers;
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            r.a,
+            r.f,
+            r.b,
+            r.c,
+            r.d,
+            r.e,
+            r.h,
+            r.l,
+            r.sp,
+            r.pc,
+            self.pcmem[0],
+            self.pcmem[1],
+            self.pcmem[2],
+            self.pcmem[3],
+        )
+    }
+}
+
+/// Receives an entry before every instruction executed, can be set with
+/// [`GameBoy::set_trace_sink`](crate::GameBoy::set_trace_sink)
+pub trait TraceSink {
+    fn trace(&mut self, entry: &TraceEntry);
+}
+
+/// Writes every entry as a line in the Gameboy Doctor format.
+///
+/// If `extended` is `true`, ` LY:00 CYC:0` is added at the end of every
+/// line, which is useful for timing bugs, but cannot be compared with the
+/// Gameboy Doctor logs directly.
+pub struct WriterTraceSink<W: Write> {
+    writer: W,
+    extended: bool,
+}
+
+impl<W: Write> WriterTraceSink<W> {
+    pub fn new(writer: W, extended: bool) -> Self {
+        Self { writer, extended }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> TraceSink for WriterTraceSink<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        // errors are ignored, as there is no way to report them from
+        // inside the emulation
+        let _ = if self.extended {
+            writeln!(
+                self.writer,
+                "{} LY:{:02X} CYC:{}",
+                entry, entry.ly, entry.cycles
+            )
+        } else {
+            writeln!(self.writer, "{}", entry)
+        };
+    }
+}
+
+/// Holds the trace sink and counts the cycles since tracing started
+pub struct Tracer {
+    sink: Box<dyn TraceSink>,
+    cycles: u64,
+}
+
+impl Tracer {
+    pub fn new(sink: Box<dyn TraceSink>) -> Self {
+        Self { sink, cycles: 0 }
+    }
+
+    pub fn add_cycles(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    pub fn trace(&mut self, registers: CpuRegisters, pcmem: [u8; 4], ly: u8) {
+        self.sink.trace(&TraceEntry {
+            registers,
+            pcmem,
+            ly,
+            cycles: self.cycles,
+        });
+    }
+
+    pub fn into_sink(self) -> Box<dyn TraceSink> {
+        self.sink
+    }
+}
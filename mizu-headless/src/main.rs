@@ -0,0 +1,482 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use mizu_core::{
+    BreakReason, GameBoy, GameboyConfig, HeaderValidation, JoypadButton, Printer, WriterTraceSink,
+    CAMERA_HEIGHT, CAMERA_WIDTH,
+};
+
+use clap::{App, Arg};
+
+const TV_WIDTH: u32 = 160;
+const TV_HEIGHT: u32 = 144;
+/// The sample rate of the audio produced by `mizu-core`
+const SAMPLE_RATE: u32 = 44100;
+/// 70224 dots per frame, the mapper is clocked every 2 dots
+const MAPPER_CLOCKS_PER_FRAME: u32 = 70224 / 2;
+
+struct InputEvent {
+    frame: u32,
+    button: JoypadButton,
+    pressed: bool,
+}
+
+fn parse_button(name: &str) -> Option<JoypadButton> {
+    Some(match name.to_lowercase().as_str() {
+        "start" => JoypadButton::Start,
+        "select" => JoypadButton::Select,
+        "b" => JoypadButton::B,
+        "a" => JoypadButton::A,
+        "down" => JoypadButton::Down,
+        "up" => JoypadButton::Up,
+        "left" => JoypadButton::Left,
+        "right" => JoypadButton::Right,
+        _ => return None,
+    })
+}
+
+/// Input scripts are text files where each line is `<frame> <press|release> <button>`,
+/// the input is applied before emulating the frame with that number (starting from 0).
+/// Empty lines and lines starting with `#` are ignored.
+fn parse_input_script(path: &str) -> Result<Vec<InputEvent>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut events = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = || format!("{}:{}: invalid input line {:?}", path, i + 1, line);
+
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(error().into());
+        }
+
+        let frame = parts[0].parse::<u32>().map_err(|_| error())?;
+        let pressed = match parts[1] {
+            "press" => true,
+            "release" => false,
+            _ => return Err(error().into()),
+        };
+        let button = parse_button(parts[2]).ok_or_else(error)?;
+
+        events.push(InputEvent {
+            frame,
+            button,
+            pressed,
+        });
+    }
+
+    Ok(events)
+}
+
+/// Loads a PNG image as a camera frame, the image is converted to greyscale
+/// and scaled to the size of the camera sensor
+fn load_camera_frame(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info()?;
+
+    let mut buffer = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buffer)?;
+
+    let channels = info.color_type.samples();
+    let (width, height) = (info.width as usize, info.height as usize);
+
+    let mut frame = Vec::with_capacity(CAMERA_WIDTH * CAMERA_HEIGHT);
+    for y in 0..CAMERA_HEIGHT {
+        for x in 0..CAMERA_WIDTH {
+            let src_x = x * width / CAMERA_WIDTH;
+            let src_y = y * height / CAMERA_HEIGHT;
+            let pixel = &buffer[(src_y * width + src_x) * channels..][..channels];
+
+            let grey = if channels >= 3 {
+                (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000
+            } else {
+                pixel[0] as u32
+            };
+            frame.push(grey as u8);
+        }
+    }
+
+    Ok(frame)
+}
+
+/// Loads a single image, or all the PNG images in a directory sorted by name
+/// to be used as a frame sequence
+fn load_camera_frames(path: &str) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    let path = Path::new(path);
+
+    if path.is_dir() {
+        let mut files = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.retain(|file| file.extension().is_some_and(|ext| ext == "png"));
+        files.sort();
+
+        if files.is_empty() {
+            return Err(format!("no PNG images found in {}", path.display()).into());
+        }
+
+        files.iter().map(|file| load_camera_frame(file)).collect()
+    } else {
+        Ok(vec![load_camera_frame(path)?])
+    }
+}
+
+fn save_png(path: &Path, screen_buffer: &[u8]) -> Result<(), Box<dyn Error>> {
+    save_png_sized(path, screen_buffer, TV_WIDTH, TV_HEIGHT)
+}
+
+fn save_png_sized(
+    path: &Path,
+    buffer: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<(), Box<dyn Error>> {
+    let file = BufWriter::new(File::create(path)?);
+
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(buffer)?;
+
+    Ok(())
+}
+
+fn save_wav(path: &Path, samples: &[f32]) -> Result<(), Box<dyn Error>> {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+
+    // the samples from the gameboy are (right, left), but wav is (left, right)
+    for sample in samples.chunks(2) {
+        writer.write_sample(sample[1])?;
+        writer.write_sample(sample[0])?;
+    }
+
+    writer.finalize()?;
+
+    Ok(())
+}
+
+fn save_registers(path: &Path, gameboy: &GameBoy) -> Result<(), Box<dyn Error>> {
+    let registers = gameboy.cpu_registers();
+    let mut file = File::create(path)?;
+
+    writeln!(
+        file,
+        "A: {:02X}\nF: {:02X}\nB: {:02X}\nC: {:02X}\nD: {:02X}\nE: {:02X}\nH: {:02X}\nL: {:02X}\nSP: {:04X}\nPC: {:04X}",
+        registers.a,
+        registers.f,
+        registers.b,
+        registers.c,
+        registers.d,
+        registers.e,
+        registers.h,
+        registers.l,
+        registers.sp,
+        registers.pc,
+    )?;
+
+    Ok(())
+}
+
+fn run(
+    gameboy: &mut GameBoy,
+    frames: u32,
+    mut inputs: Vec<InputEvent>,
+    screenshot_frames: &[u32],
+    camera_frames: &[Vec<u8>],
+    printer: Option<&RefCell<Printer>>,
+    output: &Path,
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(output)?;
+
+    // stable sort, so inputs of the same frame keep the order in the scripts
+    inputs.sort_by_key(|event| event.frame);
+    let mut inputs = inputs.into_iter().peekable();
+
+    let mut audio = Vec::new();
+    // the frames where the strength of the rumble motor changed
+    let mut rumble_changes = Vec::new();
+    let mut rumble_strength = 0;
+
+    for frame in 0..frames {
+        while inputs.peek().is_some_and(|event| event.frame <= frame) {
+            let event = inputs.next().unwrap();
+            if event.pressed {
+                gameboy.press_joypad(event.button);
+            } else {
+                gameboy.release_joypad(event.button);
+            }
+        }
+
+        // a sequence is played one image per frame, and loops
+        if camera_frames.len() > 1 || (frame == 0 && !camera_frames.is_empty()) {
+            gameboy.set_camera_frame(&camera_frames[frame as usize % camera_frames.len()]);
+        }
+
+        if let Some(BreakReason::CpuLocked(addr)) = gameboy.clock_for_frame() {
+            eprintln!("[WARN] CPU hung at ${:04X} in frame {}", addr, frame);
+            // the frame stopped at the lock, finish it
+            gameboy.clock_for_frame();
+        }
+        audio.extend(gameboy.audio_buffer());
+
+        // the percentage of the frame the motor was on
+        let strength = (gameboy.rumble_cycles() * 100 / MAPPER_CLOCKS_PER_FRAME).min(100);
+        if strength != rumble_strength {
+            rumble_strength = strength;
+            rumble_changes.push(format!("{} {}", frame, strength));
+        }
+
+        if screenshot_frames.contains(&frame) {
+            save_png(
+                &output.join(format!("frame_{}.png", frame)),
+                gameboy.screen_buffer(),
+            )?;
+        }
+    }
+
+    // flush the trace file if any
+    gameboy.set_trace_sink(None);
+    gameboy.flush_sram()?;
+
+    save_png(&output.join("final.png"), gameboy.screen_buffer())?;
+    if let Some(printer) = printer {
+        let printer = printer.borrow();
+        let (width, height) = printer.get_image_size();
+        if width * height != 0 {
+            save_png_sized(
+                &output.join("printer.png"),
+                printer.get_image_buffer(),
+                width,
+                height,
+            )?;
+        }
+    }
+    save_wav(&output.join("audio.wav"), &audio)?;
+    save_registers(&output.join("registers.txt"), gameboy)?;
+    if !rumble_changes.is_empty() {
+        fs::write(output.join("rumble.txt"), rumble_changes.join("\n") + "\n")?;
+    }
+    fs::write(output.join("state.mizu"), gameboy.save_state()?)?;
+
+    Ok(())
+}
+
+fn main() {
+    let matches = App::new("mizu-headless")
+        .version("1.0")
+        .author("Amjad Alsharafi")
+        .about("Runs a Gameboy ROM for a number of frames without a display or audio device")
+        .arg(Arg::with_name("rom").required(true))
+        .arg(Arg::with_name("boot_rom"))
+        .arg(
+            Arg::with_name("dmg")
+                .long("dmg")
+                .short("d")
+                .help("Operate the emulator in DMG mode"),
+        )
+        .arg(
+            Arg::with_name("frames")
+                .long("frames")
+                .short("n")
+                .required(true)
+                .takes_value(true)
+                .help("The number of frames to emulate"),
+        )
+        .arg(
+            Arg::with_name("input")
+                .long("input")
+                .short("i")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Input script, each line is `<frame> <press|release> <button>`"),
+        )
+        .arg(
+            Arg::with_name("screenshot")
+                .long("screenshot")
+                .short("s")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Save a screenshot after emulating this frame (starting from 0)"),
+        )
+        .arg(
+            Arg::with_name("cheat")
+                .long("cheat")
+                .short("c")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Enable a Game Genie or GameShark cheat code"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .short("t")
+                .takes_value(true)
+                .help("Write an instruction trace in the Gameboy Doctor format to this file"),
+        )
+        .arg(
+            Arg::with_name("trace-extended")
+                .long("trace-extended")
+                .requires("trace")
+                .help("Add the LY and the cycle count to every line of the trace"),
+        )
+        .arg(
+            Arg::with_name("camera")
+                .long("camera")
+                .takes_value(true)
+                .help("A PNG image, or a directory of PNG images played one per frame, used as the input of the Game Boy Camera"),
+        )
+        .arg(
+            Arg::with_name("printer")
+                .long("printer")
+                .short("p")
+                .help("Connect a printer, the printed image is saved in the output directory"),
+        )
+        .arg(
+            Arg::with_name("validation")
+                .long("validation")
+                .possible_values(&["strict", "warn", "ignore"])
+                .default_value("warn")
+                .takes_value(true)
+                .help("How to handle invalid cartridge headers"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .default_value(".")
+                .takes_value(true)
+                .help("The directory to save the screenshots, audio and final state in"),
+        )
+        .get_matches();
+
+    let is_dmg = matches.is_present("dmg");
+    let rom_file = matches.value_of("rom").expect("rom file argument");
+    let boot_rom_file = matches.value_of("boot_rom");
+    let output = Path::new(matches.value_of("output").expect("output argument"));
+
+    let frames = matches
+        .value_of("frames")
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or_else(|| {
+            eprintln!("[ERROR] frames must be a positive integer");
+            std::process::exit(1);
+        });
+
+    let screenshot_frames = matches
+        .values_of("screenshot")
+        .map(|values| {
+            values
+                .map(|s| {
+                    s.parse::<u32>().unwrap_or_else(|_| {
+                        eprintln!("[ERROR] screenshot frame must be a positive integer");
+                        std::process::exit(1);
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut inputs = Vec::new();
+    for script in matches.values_of("input").into_iter().flatten() {
+        match parse_input_script(script) {
+            Ok(events) => inputs.extend(events),
+            Err(e) => {
+                eprintln!("[ERROR] {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let header_validation = matches
+        .value_of("validation")
+        .expect("validation argument")
+        .parse::<HeaderValidation>()
+        .expect("validation is one of the possible values");
+
+    let config = GameboyConfig {
+        is_dmg,
+        header_validation,
+    };
+
+    let mut gameboy = GameBoy::new(rom_file, boot_rom_file, config).unwrap_or_else(|e| {
+        eprintln!("[ERROR] could not load the rom: {}", e);
+        std::process::exit(1);
+    });
+    for warning in gameboy.cartridge_warnings() {
+        eprintln!("[WARN] {}", warning);
+    }
+
+    for code in matches.values_of("cheat").into_iter().flatten() {
+        if let Err(e) = gameboy.add_cheat(code, "") {
+            eprintln!("[ERROR] {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(trace_file) = matches.value_of("trace") {
+        match File::create(trace_file) {
+            Ok(file) => {
+                gameboy.set_trace_sink(Some(Box::new(WriterTraceSink::new(
+                    BufWriter::new(file),
+                    matches.is_present("trace-extended"),
+                ))));
+            }
+            Err(e) => {
+                eprintln!("[ERROR] could not create the trace file: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let camera_frames = match matches.value_of("camera").map(load_camera_frames) {
+        Some(Ok(frames)) => frames,
+        Some(Err(e)) => {
+            eprintln!("[ERROR] could not load the camera images: {}", e);
+            std::process::exit(1);
+        }
+        None => Vec::new(),
+    };
+
+    let printer = if matches.is_present("printer") {
+        let printer = Rc::new(RefCell::new(Printer::default()));
+        gameboy.connect_device(printer.clone());
+        Some(printer)
+    } else {
+        None
+    };
+
+    if let Err(e) = run(
+        &mut gameboy,
+        frames,
+        inputs,
+        &screenshot_frames,
+        &camera_frames,
+        printer.as_deref(),
+        output,
+    ) {
+        eprintln!("[ERROR] {}", e);
+        std::process::exit(1);
+    }
+}
@@ -4,7 +4,7 @@ mod printer_front;
 use audio::AudioPlayer;
 use printer_front::MizuPrinter;
 
-use mizu_core::{GameBoy, GameboyConfig, JoypadButton};
+use mizu_core::{BreakReason, GameBoy, GameboyConfig, HeaderValidation, JoypadButton};
 
 use sfml::{
     graphics::{Color, FloatRect, Image, RenderTarget, RenderWindow, Sprite, Texture, View},
@@ -18,6 +18,8 @@ pub const TV_WIDTH: u32 = 160;
 pub const TV_HEIGHT: u32 = 144;
 const DEFAULT_SCALE: u32 = 5;
 const DEFAULT_FPS: u32 = 60;
+/// Save the SRAM every 10 seconds of emulation if it was modified
+const SRAM_AUTOSAVE_FRAMES: u32 = 600;
 
 struct GameboyFront {
     gameboy: GameBoy,
@@ -75,11 +77,15 @@ impl GameboyFront {
 
         loop {
             self.window.set_title(&format!(
-                "mizu - {} - FPS: {} - printer {}connected",
+                "mizu - {} - FPS: {} - printer {}connected{}",
                 self.gameboy.game_title(),
                 (1. / t.elapsed().as_secs_f64()).round(),
                 // the format! has "{}connected", so we just fill `"dis"` if needed
                 if self.printer.is_some() { "" } else { "dis" },
+                self.gameboy
+                    .cpu_locked_at()
+                    .map(|addr| format!(" - CPU hung at ${:04X}", addr))
+                    .unwrap_or_default(),
             ));
 
             t = std::time::Instant::now();
@@ -89,7 +95,17 @@ impl GameboyFront {
                 break;
             }
 
-            self.gameboy.clock_for_frame();
+            self.update_tilt();
+
+            if let Some(BreakReason::CpuLocked(addr)) = self.gameboy.clock_for_frame() {
+                eprintln!("[WARN] CPU hung at ${:04X}", addr);
+                // the frame stopped at the lock, finish it
+                self.gameboy.clock_for_frame();
+            }
+
+            if let Some(err) = self.gameboy.take_sram_autosave_error() {
+                eprintln!("[ERROR] could not save the SRAM: {}", err);
+            }
 
             let buffer = self.gameboy.audio_buffer();
 
@@ -116,6 +132,10 @@ impl GameboyFront {
             // frame limiting, must be last
             self.window.display();
         }
+
+        if let Err(err) = self.gameboy.flush_sram() {
+            eprintln!("[ERROR] could not save the SRAM: {}", err);
+        }
     }
 
     /// returns `true` if the app should close
@@ -189,6 +209,16 @@ impl GameboyFront {
         false
     }
 
+    /// Tilt using the arrow keys, for MBC7 games
+    fn update_tilt(&mut self) {
+        let axis = |negative: Key, positive: Key| {
+            positive.is_pressed() as u8 as f32 - negative.is_pressed() as u8 as f32
+        };
+
+        self.gameboy
+            .set_tilt(axis(Key::Left, Key::Right), axis(Key::Up, Key::Down));
+    }
+
     fn update_fps(&mut self) {
         self.window.set_framerate_limit(self.fps);
     }
@@ -277,6 +307,14 @@ fn main() {
                 .takes_value(true).
                 help("Specify the starting emulation speed in FPS, 0 for unlimited"),
         )
+        .arg(
+            Arg::with_name("validation")
+                .long("validation")
+                .possible_values(&["strict", "warn", "ignore"])
+                .default_value("warn")
+                .takes_value(true)
+                .help("How to handle invalid cartridge headers"),
+        )
         .get_matches();
 
     let is_dmg = matches.is_present("dmg");
@@ -311,9 +349,22 @@ fn main() {
         })
         .unwrap_or(DEFAULT_FPS);
 
-    let config = GameboyConfig { is_dmg };
+    let header_validation = matches
+        .value_of("validation")
+        .expect("validation argument")
+        .parse::<HeaderValidation>()
+        .expect("validation is one of the possible values");
+
+    let config = GameboyConfig {
+        is_dmg,
+        header_validation,
+    };
 
-    let gameboy = GameBoy::new(rom_file, boot_rom_file, config).unwrap();
+    let mut gameboy = GameBoy::new(rom_file, boot_rom_file, config).unwrap();
+    for warning in gameboy.cartridge_warnings() {
+        eprintln!("[WARN] {}", warning);
+    }
+    gameboy.set_sram_autosave(Some(SRAM_AUTOSAVE_FRAMES));
 
     let mut gameboy_front = GameboyFront::new(gameboy, fps, scale);
 